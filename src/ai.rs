@@ -0,0 +1,410 @@
+use bevy::prelude::*;
+
+use super::aiming::{aim_shot, calculate_path, prediction_bodies, MAX_ANGLE, MIN_POWER, MAX_POWER};
+use super::app_state::AppState;
+use super::asteroids::{Asteroid, AsteroidMotion};
+use super::base::{Base, BaseOwner};
+use super::game::TurnAction;
+use super::gravity::{GravitySource, GravityBody};
+use super::inventory::Inventory;
+use super::map_gen::{MapRng, time_seed};
+use super::orbit::OrbitSettings;
+use super::player::{Player, team_mates};
+use super::turn::{TurnPhase, TurnState};
+use super::weapon::WeaponType;
+
+// Computer players. On their turn the shot is found by trying angles and powers with the
+// same prediction as the aiming tracer, keeping whichever lands closest to an enemy base
+
+// Shots landing this close to a friendly base are ruled out
+const SAFE_DISTANCE : f32 = 60.0;
+// Shots that miss by more than this are worth a homing rocket
+const HOMING_MISS : f32 = 60.0;
+// Shots that miss by more than this are not worth taking with a damaged base
+const HOPELESS_MISS : f32 = 150.0;
+
+// Who decides what a player does on their turn
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Controller {
+    Human,
+    Computer(Difficulty)
+}
+
+impl Default for Controller {
+    fn default() -> Self {
+        Controller::Human
+    }
+}
+
+impl Controller {
+    // Cycled through in the lobby
+    pub fn next(self : &Self) -> Controller {
+        match *self {
+            Controller::Human => Controller::Computer(Difficulty::Easy),
+            Controller::Computer(Difficulty::Easy) => Controller::Computer(Difficulty::Normal),
+            Controller::Computer(Difficulty::Normal) => Controller::Computer(Difficulty::Hard),
+            Controller::Computer(Difficulty::Hard) => Controller::Human
+        }
+    }
+
+    pub fn label(self : &Self) -> &'static str {
+        match *self {
+            Controller::Human => "Human",
+            Controller::Computer(Difficulty::Easy) => "CPU Easy",
+            Controller::Computer(Difficulty::Normal) => "CPU Normal",
+            Controller::Computer(Difficulty::Hard) => "CPU Hard"
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard
+}
+
+impl Difficulty {
+    // Number of shots tried when searching for one that hits
+    pub fn budget(self : &Self) -> usize {
+        match *self {
+            Difficulty::Easy => 30,
+            Difficulty::Normal => 120,
+            Difficulty::Hard => 400
+        }
+    }
+    // Largest error added to the angle of the shot found
+    fn angle_noise(self : &Self) -> f32 {
+        match *self {
+            Difficulty::Easy => 0.12,
+            Difficulty::Normal => 0.03,
+            Difficulty::Hard => 0.0
+        }
+    }
+    // Largest error added to the power of the shot found, as a fraction of it
+    fn power_noise(self : &Self) -> f32 {
+        match *self {
+            Difficulty::Easy => 0.1,
+            Difficulty::Normal => 0.03,
+            Difficulty::Hard => 0.0
+        }
+    }
+}
+
+// Everything needed to try out a shot from a base
+pub struct ShotProblem<'a> {
+    pub base : &'a GlobalTransform,
+    pub bodies : Vec<GravityBody>,
+    // Shots stop at the first asteroid they hit. Orbiting asteroids are taken to be
+    // where they are now
+    pub rocks : Vec<(&'a Asteroid, &'a Transform)>,
+    // Bases to aim for and bases to keep away from
+    pub targets : Vec<Vec2>,
+    pub friends : Vec<Vec2>,
+    // How long the weapon flies before it goes off by itself
+    pub fuel : f32
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Shot {
+    pub angle : f32,
+    pub power : f32,
+    // Distance from where the shot goes off to the nearest target
+    pub miss : f32
+}
+
+impl<'a> ShotProblem<'a> {
+    // Where a shot goes off, either on the first asteroid it hits or where it runs out of fuel
+    pub fn blast(self : &Self, angle : f32, power : f32) -> Vec2 {
+        let (start, thrust) = aim_shot(self.base, angle, power);
+        let path = calculate_path(&self.bodies, start, self.fuel, thrust);
+        path.iter().find(
+            |pos| self.rocks.iter().any(|(asteroid, transform)| asteroid.contains(transform, **pos))
+        ).or(path.last()).cloned().unwrap_or(start)
+    }
+
+    fn try_shot(self : &Self, angle : f32, power : f32) -> Shot {
+        let blast = self.blast(angle, power);
+        let nearest = |bases : &Vec<Vec2>| bases.iter().map(
+            |base| base.distance(blast)
+        ).fold(f32::INFINITY, f32::min);
+        let mut miss = nearest(&self.targets);
+        if nearest(&self.friends) < SAFE_DISTANCE {
+            miss += 1000.0;
+        }
+        Shot { angle : angle, power : power, miss : miss }
+    }
+
+    // Try a coarse grid over every angle and power then keep refining around the best
+    // shot so far with a finer grid until the budget runs out
+    pub fn search(self : &Self, budget : usize) -> Option<Shot> {
+        if self.targets.is_empty() {
+            return None;
+        }
+        let side = ((budget / 2) as f32).sqrt().max(2.0) as usize;
+        let mut angle_step = 2.0 * MAX_ANGLE / (side - 1) as f32;
+        let mut power_step = (MAX_POWER - MIN_POWER) / (side - 1) as f32;
+        let mut best : Option<Shot> = None;
+        let mut tried = 0;
+        let consider = |shot : Shot, best : &mut Option<Shot>| {
+            if best.map_or(true, |best| shot.miss < best.miss) {
+                *best = Some(shot);
+            }
+        };
+        for i in 0..side {
+            for j in 0..side {
+                let shot = self.try_shot(
+                    -MAX_ANGLE + i as f32 * angle_step,
+                    MIN_POWER + j as f32 * power_step
+                );
+                consider(shot, &mut best);
+                tried += 1;
+            }
+        }
+        while tried + 8 <= budget {
+            angle_step /= 2.0;
+            power_step /= 2.0;
+            let centre = best?;
+            for i in -1..=1 {
+                for j in -1..=1 {
+                    if i == 0 && j == 0 {
+                        continue;
+                    }
+                    let shot = self.try_shot(
+                        (centre.angle + i as f32 * angle_step).clamp(-MAX_ANGLE, MAX_ANGLE),
+                        (centre.power + j as f32 * power_step).clamp(MIN_POWER, MAX_POWER)
+                    );
+                    consider(shot, &mut best);
+                    tried += 1;
+                }
+            }
+        }
+        best
+    }
+}
+
+// What a computer player has decided to do this turn
+#[derive(Clone, Copy, Debug)]
+enum AiPlan {
+    Fire(WeaponType),
+    Repair,
+    Skip
+}
+
+// Resources
+
+pub struct AiSettings {
+    // Seconds a computer player spends showing its aim before acting
+    pub think_time : f32,
+    // Seed for the errors added to the aim, a fresh one is picked each game if not set
+    pub seed : Option<u64>
+}
+
+impl Default for AiSettings {
+    fn default() -> Self {
+        AiSettings { think_time : 1.0, seed : None }
+    }
+}
+
+// The turn being played, so a computer player only plans once a turn
+pub struct AiTurn {
+    base : Option<Entity>,
+    computer : bool,
+    plan : Option<AiPlan>,
+    wait : f32,
+    rng : MapRng
+}
+
+impl Default for AiTurn {
+    fn default() -> Self {
+        // Seeded again as each game starts
+        AiTurn {
+            base : None,
+            computer : false,
+            plan : None,
+            wait : 0.0,
+            rng : MapRng::new(0)
+        }
+    }
+}
+
+impl AiTurn {
+    // Whether it is a computer player's turn, so the controls are left alone
+    pub fn active(self : &Self) -> bool {
+        self.computer
+    }
+}
+
+// Pick a weapon and search for a shot with it. Without anything worth firing a damaged
+// base is repaired instead
+fn plan_turn(
+    difficulty : Difficulty,
+    problem : &mut ShotProblem,
+    inventory : &Inventory,
+    base : &Base,
+    rng : &mut MapRng
+) -> (AiPlan, Option<Shot>) {
+    let in_stock = |weapon_type : &WeaponType| inventory.count(*weapon_type) > 0;
+    let fallback = if base.health < base.max_health { AiPlan::Repair } else { AiPlan::Skip };
+    // A volley covers for shaky aim. The shot search only knows about weapons which go
+    // off where they hit, so mines, borers and yanks are kept back
+    let preferred = if difficulty.angle_noise() > 0.0 {
+        [WeaponType::MultiRocket, WeaponType::Rocket, WeaponType::HomingRocket]
+    } else {
+        [WeaponType::Rocket, WeaponType::MultiRocket, WeaponType::HomingRocket]
+    };
+    let mut choices = preferred.iter().chain(
+        WeaponType::ALL.iter().filter(|weapon_type| weapon_type.explodes_on_impact())
+    );
+    let mut weapon_type = match choices.find(|weapon_type| in_stock(weapon_type)) {
+        Some(weapon_type) => *weapon_type,
+        None => return (fallback, None)
+    };
+    problem.fuel = weapon_type.fuel();
+    let mut shot = match problem.search(difficulty.budget()) {
+        Some(shot) => shot,
+        None => return (AiPlan::Skip, None)
+    };
+    if shot.miss > HOPELESS_MISS && base.health < 0.5 * base.max_health {
+        return (AiPlan::Repair, None);
+    }
+    if shot.miss > HOMING_MISS && in_stock(&WeaponType::HomingRocket) {
+        weapon_type = WeaponType::HomingRocket;
+    }
+    let angle_noise = difficulty.angle_noise();
+    let power_noise = difficulty.power_noise();
+    shot.angle = (shot.angle + rng.range(-angle_noise, angle_noise)).clamp(-MAX_ANGLE, MAX_ANGLE);
+    shot.power = (shot.power * (1.0 + rng.range(-power_noise, power_noise))).clamp(MIN_POWER, MAX_POWER);
+    (AiPlan::Fire(weapon_type), Some(shot))
+}
+
+// Systems
+
+// Plan the turn of a computer player as it starts, aim and then act once the think time
+// is up
+fn ai_turn_system(
+    time : Res<Time>,
+    settings : Res<AiSettings>,
+    orbits : Res<OrbitSettings>,
+    mut ai_turn : ResMut<AiTurn>,
+    mut turn_state : ResMut<TurnState>,
+    base_query : Query<(&Base, &BaseOwner, &GlobalTransform)>,
+    player_query : Query<&Player>,
+    mut inventory_query : Query<&mut Inventory>,
+    source_query : Query<(&GravitySource, &Transform, Option<&AsteroidMotion>)>,
+    asteroid_query : Query<(&Asteroid, &Transform)>,
+    mut actions : EventWriter<TurnAction>
+) {
+    if turn_state.paused() {
+        return;
+    }
+    let base = match turn_state.active_base {
+        Some(base) if turn_state.phase == TurnPhase::Aiming => base,
+        _ => {
+            ai_turn.base = None;
+            ai_turn.computer = false;
+            ai_turn.plan = None;
+            return;
+        }
+    };
+    let (active_base, owner, base_transform) = match base_query.get(base) {
+        Ok(found) => found,
+        Err(_) => return
+    };
+    if ai_turn.base != Some(base) {
+        // Forget the last turn first, so a human's turn is never played from it
+        ai_turn.base = Some(base);
+        ai_turn.computer = false;
+        ai_turn.plan = None;
+        let difficulty = match player_query.get(owner.entity).map(|player| player.controller) {
+            Ok(Controller::Computer(difficulty)) => difficulty,
+            _ => return
+        };
+        let inventory = match inventory_query.get(owner.entity) {
+            Ok(inventory) => inventory,
+            Err(_) => return
+        };
+        let mut targets = Vec::new();
+        let mut friends = Vec::new();
+        for (_, base_owner, transform) in base_query.iter() {
+            let pos = Vec2::new(transform.translation.x, transform.translation.y);
+            if base_owner.entity == owner.entity || team_mates(&player_query, owner.entity, base_owner.entity) {
+                friends.push(pos);
+            } else {
+                targets.push(pos);
+            }
+        }
+        let mut problem = ShotProblem {
+            base : base_transform,
+            bodies : prediction_bodies(&source_query, &orbits),
+            rocks : asteroid_query.iter().collect(),
+            targets : targets,
+            friends : friends,
+            fuel : 0.0
+        };
+        let (plan, shot) = plan_turn(difficulty, &mut problem, inventory, active_base, &mut ai_turn.rng);
+        if let Some(shot) = shot {
+            turn_state.firing_angle = shot.angle;
+            turn_state.power = shot.power;
+        }
+        ai_turn.computer = true;
+        ai_turn.plan = Some(plan);
+        ai_turn.wait = settings.think_time;
+    }
+    if ai_turn.plan.is_none() {
+        return;
+    }
+    ai_turn.wait -= time.delta_seconds();
+    if ai_turn.wait > 0.0 {
+        return;
+    }
+    match ai_turn.plan.take() {
+        Some(AiPlan::Fire(weapon_type)) => {
+            if let Ok(mut inventory) = inventory_query.get_mut(owner.entity) {
+                inventory.selected = weapon_type;
+            }
+            actions.send(TurnAction::Fire);
+        },
+        Some(AiPlan::Repair) => actions.send(TurnAction::Repair),
+        Some(AiPlan::Skip) => actions.send(TurnAction::Skip),
+        None => {}
+    }
+}
+
+fn setup_ai(
+    settings : Res<AiSettings>,
+    mut ai_turn : ResMut<AiTurn>
+) {
+    ai_turn.rng = MapRng::new(settings.seed.unwrap_or_else(time_seed));
+}
+
+fn teardown_ai(
+    mut ai_turn : ResMut<AiTurn>
+) {
+    ai_turn.base = None;
+    ai_turn.computer = false;
+    ai_turn.plan = None;
+}
+
+// Plugins
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AiSettings>()
+           .init_resource::<AiTurn>()
+           .add_system_set(
+               SystemSet::on_enter(AppState::InGame)
+                 .with_system(setup_ai.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(AppState::InGame)
+                 .with_system(ai_turn_system.system())
+           )
+           .add_system_set(
+               SystemSet::on_exit(AppState::InGame)
+                 .with_system(teardown_ai.system())
+           );
+    }
+}
@@ -4,8 +4,17 @@ use std::collections::HashMap;
 
 use super::turn::{TurnPhase, TurnState, TurnStart, TurnFiring};
 use super::app_state::AppState;
-use super::asteroids::{Asteroid, calculate_gravity};
+use super::ai::AiTurn;
+use super::gravity::{GravitySource, GravityBody, body_gravity, step_bodies};
 use super::base::Base;
+use super::asteroids::AsteroidMotion;
+use super::orbit::OrbitSettings;
+use super::physics::{PHYSICS_STEP, flight_step};
+
+// Limits of the aim, the angle is either side of straight up from the base
+pub const MAX_ANGLE : f32 = std::f32::consts::FRAC_PI_2;
+pub const MIN_POWER : f32 = 30.0;
+pub const MAX_POWER : f32 = 200.0;
 
 // Components
 
@@ -32,9 +41,11 @@ pub struct AimingTracer {
 fn aiming_system(
     key_input: Res<Input<KeyCode>>,
     mut turn_state : ResMut<TurnState>,
+    ai_turn : Res<AiTurn>,
     time : Res<Time>
 ) {
-    if turn_state.phase == TurnPhase::Aiming {
+    // Computer players aim for themselves
+    if turn_state.phase == TurnPhase::Aiming && !ai_turn.active() {
         if key_input.pressed(KeyCode::A) {
             turn_state.firing_angle += time.delta_seconds() * 1.5;
         }
@@ -42,10 +53,7 @@ fn aiming_system(
             turn_state.firing_angle -= time.delta_seconds() * 1.5;
         }
 
-        turn_state.firing_angle = turn_state.firing_angle.clamp(
-            -std::f32::consts::FRAC_PI_2, 
-            std::f32::consts::FRAC_PI_2
-        );
+        turn_state.firing_angle = turn_state.firing_angle.clamp(-MAX_ANGLE, MAX_ANGLE);
 
         if key_input.pressed(KeyCode::W) {
             turn_state.power += time.delta_seconds() * 60.0;
@@ -54,10 +62,7 @@ fn aiming_system(
             turn_state.power -= time.delta_seconds() * 60.0;
         }
 
-        turn_state.power = turn_state.power.clamp(
-            30.0,
-            200.0
-        );
+        turn_state.power = turn_state.power.clamp(MIN_POWER, MAX_POWER);
     }
 }
 
@@ -81,46 +86,89 @@ fn aiming_ui_aiming_start(
     }
 }
 
-fn calculate_position(
-    asteroid_query : &Query<(&Asteroid, &GlobalTransform)>,
+// Where a shot fired from a base starts and the thrust it starts with, the same as the
+// launch of a real weapon
+pub fn aim_shot(base_transform : &GlobalTransform, angle : f32, power : f32) -> (Vec2, Vec2) {
+    let aim_rotation = base_transform.rotation * Quat::from_rotation_z(angle);
+    let direction = aim_rotation * Vec3::new(0.0, 1.0 ,0.0);
+    let thrust = Vec2::new(direction.x, direction.y) * power;
+    let start = direction * 12.0 + base_transform.translation;
+    (Vec2::new(start.x, start.y), thrust)
+}
+
+// The gravity sources to predict shots with, asteroids only need to be moved along with
+// the shot if they are orbiting
+pub fn prediction_bodies(
+    source_query : &Query<(&GravitySource, &Transform, Option<&AsteroidMotion>)>,
+    orbits : &OrbitSettings
+) -> Vec<GravityBody> {
+    source_query.iter().map(
+        |(source, transform, motion)| GravityBody {
+            mass : source.mass,
+            pos : Vec2::new(transform.translation.x, transform.translation.y),
+            velocity : motion.map_or(Vec2::ZERO, |motion| motion.velocity),
+            moving : orbits.enabled && motion.is_some()
+        }
+    ).collect()
+}
+
+// Every position of a shot over a time, stepped in the same way as the weapon itself
+// will be so the prediction matches. Moving gravity sources are moved along with it
+pub fn calculate_path(
+    bodies : &Vec<GravityBody>,
     start : Vec2,
     length : f32,
     initial_thrust : Vec2,
-) -> Vec2 {
-    let step = 0.02;
-    let step_num : usize = (length / step) as usize;
+) -> Vec<Vec2> {
+    let step_num : usize = (length / PHYSICS_STEP).round() as usize;
+    let mut path = Vec::with_capacity(step_num);
     let mut pos = start.clone();
     let mut thrust = initial_thrust.clone();
+    let mut bodies = bodies.clone();
+    let moving = bodies.iter().any(|body| body.moving);
     for _ in 0..step_num  {
-        thrust += calculate_gravity(asteroid_query, pos, step);
-        pos += thrust * step;
+        let gravity = body_gravity(&bodies, pos, PHYSICS_STEP);
+        let (next_pos, next_thrust) = flight_step(pos, thrust, gravity);
+        pos = next_pos;
+        thrust = next_thrust;
+        path.push(pos);
+        if moving {
+            step_bodies(&mut bodies, PHYSICS_STEP);
+        }
     }
-    pos
+    path
+}
+
+// Where a shot will be after a time
+pub fn calculate_position(
+    bodies : &Vec<GravityBody>,
+    start : Vec2,
+    length : f32,
+    initial_thrust : Vec2,
+) -> Vec2 {
+    calculate_path(bodies, start, length, initial_thrust).last().cloned().unwrap_or(start)
 }
 
 fn aiming_ui_update_system(
     turn_state : Res<TurnState>,
+    orbits : Res<OrbitSettings>,
     tracer_query : Query<(Entity, &AimingTracer)>,
     mut query : QuerySet<(
         QueryState<&GlobalTransform, With<Base>>,
         QueryState<(Entity, &mut GlobalTransform), With<AimingTracer>>,
-        QueryState<(&Asteroid, &GlobalTransform)>
+        QueryState<(&GravitySource, &Transform, Option<&AsteroidMotion>)>
     )>
 ) {
     if let Some(base) = turn_state.active_base {
         let transform_res = query.q0().get(base);
         if let Ok(base_transform) = transform_res {
-            let aim_rotation = base_transform.rotation * Quat::from_rotation_z(turn_state.firing_angle);
-            let direction = aim_rotation * Vec3::new(0.0, 1.0 ,0.0);
-            let thrust = Vec2::new(direction.x, direction.y) * turn_state.power;
-            let offset = direction * 12.0;
-            let base_pos3 = offset + base_transform.translation;
-            let base_pos = Vec2::new(base_pos3.x, base_pos3.y);
+            let (base_pos, thrust) = aim_shot(base_transform, turn_state.firing_angle, turn_state.power);
+            let bodies = prediction_bodies(&query.q2(), &orbits);
 
             // Do in 2 stages for mutabilty
             let mut positions : HashMap<Entity, Vec2> = HashMap::new();
             for (entity, trace) in tracer_query.iter() {
-                let position = calculate_position(&query.q2(), base_pos, trace.delay, thrust);
+                let position = calculate_position(&bodies, base_pos, trace.delay, thrust);
                 positions.insert(entity, position);
             }
             for (entity, mut trace_transform) in query.q1().iter_mut() {
@@ -1,6 +1,11 @@
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum AppState {
     MainMenu,
+    // Choosing who plays in the next game
+    Lobby,
     InGame,
     VictoryMenu,
+    MapEditor,
+    // Passed through on the way back into a game so the old one is torn down first
+    Restarting,
 }
\ No newline at end of file
@@ -1,123 +1,433 @@
 use bevy::prelude::*;
+use bevy::render::mesh::Indices;
+use bevy::render::render_resource::PrimitiveTopology;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
 
 use super::collide::Circle;
-use super::base::{Base, BaseDestroyed};
+use super::base::{Base, BaseDestroyed, BaseFall, BASE_WIDTH};
 use super::explosion::Explode;
 use super::app_state::AppState;
+use super::mine::Mine;
+use super::gravity::GravitySource;
+use super::orbit::OrbitSettings;
+use super::physics::{PhysicsStage, PhysicsLabel, PHYSICS_STEP};
+use super::terrain::{Terrain, angle_of, direction_of};
+use super::map::default_asteroid_texture;
 
+use std::f32::consts::PI;
+
+// Number of heights around the surface of an asteroid
+const TERRAIN_SAMPLES : usize = 72;
+
+// Mass of each unit of area of ordinary rock, asteroid densities are relative to this
+const ROCK_MASS_PER_AREA : f32 = 150.0 / PI;
+
+// Child drawing the surface of an asteroid, the mesh is built from the terrain once it is spawned
 #[derive(Component)]
-pub struct AsteroidDrawable;
+pub struct AsteroidDrawable {
+    // Path of the image drawn on the surface
+    pub texture : String
+}
 
 #[derive(Clone, Component)]
 pub struct Asteroid{
+    // Size the asteroid started at, the texture is mapped to this
     pub max_radius : f32,
-    pub radius : f32
+    // Furthest the surface reaches from the centre
+    pub radius : f32,
+    // How heavy the rock is compared to ordinary rock
+    pub density : f32,
+    pub terrain : Terrain
+}
+
+#[derive(Component)]
+pub struct AsteroidMotion {
+    pub velocity : Vec2
 }
 
 pub struct AsteroidDestroyed { 
     pub asteroid : Entity
 }
 
+// A heavily damaged asteroid breaking up into smaller ones
+pub struct AsteroidSplit {
+    pub asteroid : Entity,
+    // Where the blow landed relative to the asteroid centre
+    pub impact : Vec2,
+    pub pieces : usize
+}
+
+// Change in momentum to apply to an asteroid
+pub struct AsteroidImpulse {
+    pub asteroid : Entity,
+    pub impulse : Vec2
+}
+
 impl Asteroid {
-    pub fn bound(self : &Self, transform : &GlobalTransform) -> Circle {
+    pub fn bound(self : &Self, transform : &Transform) -> Circle {
         let centre = Vec2::new(transform.translation.x, transform.translation.y);
         Circle { radius : self.radius, centre : centre }
     }
+
+    // Mass of the rock left, so it drops as craters are blown out
+    pub fn mass(self : &Self) -> f32 {
+        ROCK_MASS_PER_AREA * self.density * self.terrain.area()
+    }
+
+    pub fn height(self : &Self, angle : f32) -> f32 {
+        self.terrain.height(angle)
+    }
+
+    pub fn footing(self : &Self, angle : f32, width : f32) -> f32 {
+        self.terrain.footing(angle, width)
+    }
+
+    pub fn contains(self : &Self, transform : &Transform, point : Vec2) -> bool {
+        let centre = Vec2::new(transform.translation.x, transform.translation.y);
+        self.terrain.contains(point - centre)
+    }
+
+    // Blow a crater out of the surface, the centre is relative to the asteroid
+    pub fn crater(self : &mut Self, centre : Vec2, radius : f32) {
+        if self.terrain.crater(centre, radius) {
+            self.radius = self.terrain.max_height();
+        }
+    }
+}
+
+// Mass of an undamaged asteroid
+pub fn asteroid_mass(radius : f32, density : f32) -> f32 {
+    ROCK_MASS_PER_AREA * density * PI * radius * radius
 }
 
-pub fn add_asteroid(commands: &mut Commands, x : f32, y : f32, texture : Handle<Image>) -> (Entity, Asteroid) {
-    let max_radius = 100.0;
+pub fn add_asteroid(
+    commands: &mut Commands, 
+    x : f32, y : f32, 
+    radius : f32, 
+    density : f32, 
+    texture : String
+) -> (Entity, Asteroid) {
     let asteroid = Asteroid{
-        max_radius : 100.0,
-        radius : 50.0
+        max_radius : radius,
+        radius : radius,
+        density : density,
+        terrain : Terrain::new(radius, TERRAIN_SAMPLES)
     };
     let id = commands.spawn().insert(asteroid.clone()).insert(Transform::from_xyz(x, y, 0.0)
     ).insert(GlobalTransform::from_xyz(x, y, 0.0)
+    ).insert(AsteroidMotion { velocity : Vec2::ZERO }
+    ).insert(GravitySource { mass : asteroid.mass() }
     ).with_children(
         |parent| {
-            parent.spawn_bundle(SpriteBundle {
-                texture: texture,
-                transform: Transform::identity(),
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(2.0*max_radius, 2.0*max_radius)),
-                    ..Default::default()
-                },
-                ..Default::default()
-            }).insert(AsteroidDrawable);
+            parent.spawn().insert(AsteroidDrawable { texture : texture }
+            ).insert(Transform::identity()
+            ).insert(GlobalTransform::identity());
         }
     ).id();
     (id, asteroid)
 }
 
-// If an asteroid's radius changes we want to update it's sprite and reposition bases on the surface
-// so they stay on the surface
+// Fan of triangles from the centre out to each terrain sample, the texture stays where it
+// was on the undamaged asteroid so craters cut into it
+fn terrain_mesh(asteroid : &Asteroid) -> Mesh {
+    let terrain = &asteroid.terrain;
+    let samples = terrain.samples();
+    let mut positions : Vec<[f32; 3]> = vec![[0.0, 0.0, 0.0]];
+    let mut uvs : Vec<[f32; 2]> = vec![[0.5, 0.5]];
+    for index in 0..samples {
+        let point = direction_of(terrain.sample_angle(index)) * terrain.sample_height(index);
+        positions.push([point.x, point.y, 0.0]);
+        uvs.push([
+            0.5 + point.x / (2.0 * asteroid.max_radius),
+            0.5 - point.y / (2.0 * asteroid.max_radius)
+        ]);
+    }
+    let normals : Vec<[f32; 3]> = vec![[0.0, 0.0, 1.0]; positions.len()];
+    let mut indices : Vec<u32> = Vec::new();
+    for index in 0..samples {
+        indices.extend_from_slice(&[0, 1 + index as u32, 1 + ((index + 1) % samples) as u32]);
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn asteroid_drawable_added(
+    mut commands : Commands,
+    drawable_query : Query<(Entity, &AsteroidDrawable, &Parent), Added<AsteroidDrawable>>,
+    asteroid_query : Query<&Asteroid>,
+    asset_server : Res<AssetServer>,
+    mut meshes : ResMut<Assets<Mesh>>,
+    mut materials : ResMut<Assets<ColorMaterial>>
+) {
+    for (entity, drawable, parent) in drawable_query.iter() {
+        if let Ok(asteroid) = asteroid_query.get(parent.0) {
+            commands.entity(entity).insert_bundle(MaterialMesh2dBundle {
+                mesh : meshes.add(terrain_mesh(asteroid)).into(),
+                material : materials.add(ColorMaterial {
+                    color : Color::WHITE,
+                    texture : Some(asset_server.load(drawable.texture.as_str()))
+                }),
+                ..Default::default()
+            });
+        }
+    }
+}
+
+// If an asteroid's surface changes we want to reposition bases and mines on the surface
+// so they stay on the surface. Bases which lose the ground under them fall
 pub fn asteroid_changed(
     query: Query<(&Asteroid, &Children), Changed<Asteroid>>,
     mut transform_query: Query<&mut Transform>,
-    bases_query: Query<&Base>,
-    asteroid_drawable_query: Query<&AsteroidDrawable>
+    mut bases_query: Query<&mut Base>,
+    mines_query: Query<&Mine>,
+    mut events_fall : EventWriter<BaseFall>
 ) {
     for (asteroid, children) in query.iter() {
-        // Reposition any bases and update the drawable
         for child in children.iter() {
             // If this is a base reposition it
-            if let Ok(base) = bases_query.get(*child) {
+            if let Ok(mut base) = bases_query.get_mut(*child) {
+                let ground = asteroid.footing(base.angle, BASE_WIDTH);
+                if ground < base.ground {
+                    events_fall.send(BaseFall { base : *child, distance : base.ground - ground });
+                    base.ground = ground;
+                }
                 if let Ok(mut transform) = transform_query.get_mut(*child) {
-                    let angle = base.angle;
-                    let radius = asteroid.radius - base.offset;
-                    transform.translation = Vec3::new(-radius * angle.sin(), radius * angle.cos(), 0.0);
-                    println!("base moved");
+                    let radius = base.ground - base.offset;
+                    transform.translation = (direction_of(base.angle) * radius).extend(0.0);
                 }
             } 
-            // Asteroid drawable children need to be scales to new size
-            if asteroid_drawable_query.get(*child).is_ok() {
+            // Mines sit on the surface in the same way
+            if let Ok(mine) = mines_query.get(*child) {
                 if let Ok(mut transform) = transform_query.get_mut(*child) {
-                    let scale = asteroid.radius / asteroid.max_radius;
-                    transform.scale = Vec3::new(scale, scale, scale);
+                    let radius = asteroid.height(mine.angle) - mine.offset;
+                    transform.translation = (direction_of(mine.angle) * radius).extend(0.0);
+                }
+            }
+        }
+    }
+}
+
+// Asteroid drawable children need the new surface
+fn asteroid_mesh_changed(
+    query: Query<(&Asteroid, &Children), Changed<Asteroid>>,
+    drawable_query: Query<&Mesh2dHandle, With<AsteroidDrawable>>,
+    mut meshes : ResMut<Assets<Mesh>>
+) {
+    for (asteroid, children) in query.iter() {
+        for child in children.iter() {
+            if let Ok(handle) = drawable_query.get(*child) {
+                if let Some(mesh) = meshes.get_mut(&handle.0) {
+                    *mesh = terrain_mesh(asteroid);
                 }
             }
         }
     }
 }
 
-pub fn calculate_gravity(
-    asteroid_query : &Query<(&Asteroid, &GlobalTransform)>,
-    position : Vec2,
-    delta_seconds : f32
-) -> Vec2 {
-    let mut force = Vec2::ZERO;
-    for (asteroid, asteroid_transform) in asteroid_query.iter() {
-        let asteroid_pos = asteroid_transform.translation;
-        let delta = position - Vec2::new(asteroid_pos.x, asteroid_pos.y);
-        if delta.length() > 1.0 {
-            let mass = delta_seconds * 150.0 * asteroid.radius * asteroid.radius;
-            let dist_sq = delta.length_squared();
-            force -= (mass / dist_sq) * delta.normalize();
+// Keep the pull of an asteroid in step with its size
+fn asteroid_gravity_changed(
+    mut query: Query<(&Asteroid, &mut GravitySource), Changed<Asteroid>>
+) {
+    for (asteroid, mut source) in query.iter_mut() {
+        source.mass = asteroid.mass();
+    }
+}
+
+fn asteroid_impulse(
+    mut events : EventReader<AsteroidImpulse>,
+    mut asteroids : Query<(&Asteroid, &mut AsteroidMotion)>
+) {
+    for event in events.iter() {
+        if let Ok((asteroid, mut motion)) = asteroids.get_mut(event.asteroid) {
+            motion.velocity += event.impulse / asteroid.mass();
+        }
+    }
+}
+
+// Drift moving asteroids, slowing down so they come to rest again unless they are orbiting.
+// Bases and mines are children so they are carried along
+fn asteroid_move(
+    mut asteroids : Query<(&mut AsteroidMotion, &mut Transform)>,
+    orbits : Res<OrbitSettings>
+) {
+    // Orbiting asteroids would spiral into each other if they slowed down
+    let damping = if orbits.enabled { 0.0 } else { 0.4 };
+    for (mut motion, mut transform) in asteroids.iter_mut() {
+        if motion.velocity.length_squared() < 0.01 {
+            continue;
+        }
+        let velocity = motion.velocity;
+        transform.translation.x += velocity.x * PHYSICS_STEP;
+        transform.translation.y += velocity.y * PHYSICS_STEP;
+        motion.velocity *= (-damping * PHYSICS_STEP).exp();
+    }
+}
+
+// Separate overlapping asteroids and bounce them off each other
+fn asteroid_collide(
+    mut asteroids : Query<(Entity, &Asteroid, &mut AsteroidMotion, &mut Transform)>
+) {
+    let restitution = 0.5;
+    // Do in 2 stages for mutabilty
+    let bodies : Vec<(Entity, Vec2, Vec2, f32, f32)> = asteroids.iter().map(
+        |(entity, asteroid, motion, transform)| (
+            entity, 
+            Vec2::new(transform.translation.x, transform.translation.y), 
+            motion.velocity, 
+            asteroid.radius, 
+            asteroid.mass()
+        )
+    ).collect();
+    let mut changes : Vec<(Entity, Vec2, Vec2)> = Vec::new();
+    for (i, (entity_a, pos_a, vel_a, radius_a, mass_a)) in bodies.iter().enumerate() {
+        for (entity_b, pos_b, vel_b, radius_b, mass_b) in bodies.iter().skip(i + 1) {
+            let delta = *pos_b - *pos_a;
+            let overlap = radius_a + radius_b - delta.length();
+            if overlap <= 0.0 || delta.length() < 1e-3 {
+                continue;
+            }
+            let normal = delta.normalize();
+            let total_mass = mass_a + mass_b;
+            let closing = (*vel_b - *vel_a).dot(normal);
+            let impulse = if closing < 0.0 {
+                -(1.0 + restitution) * closing / (1.0 / mass_a + 1.0 / mass_b)
+            } else {
+                0.0
+            };
+            changes.push((*entity_a, -normal * overlap * *mass_b / total_mass, -normal * impulse / *mass_a));
+            changes.push((*entity_b, normal * overlap * *mass_a / total_mass, normal * impulse / *mass_b));
+        }
+    }
+    for (entity, shift, velocity_change) in changes {
+        if let Ok((_, _, mut motion, mut transform)) = asteroids.get_mut(entity) {
+            transform.translation += Vec3::new(shift.x, shift.y, 0.0);
+            motion.velocity += velocity_change;
         }
     }
-    return force;
 }
 
 fn damage_asteroid(
     mut asteroids : Query<(&mut Asteroid, &GlobalTransform, Entity)>,
     mut events : EventReader<Explode>,
-    mut event_destroy : EventWriter<AsteroidDestroyed>
+    mut event_destroy : EventWriter<AsteroidDestroyed>,
+    mut event_split : EventWriter<AsteroidSplit>
 ) {
-    let max_dist = 20.0;
+    let crater_scale = 0.4;
     let min_radius = 8.0;
+    // Asteroids at least this big break up if the crater is large compared to what is left
+    let min_split_radius = 24.0;
+    let split_fraction = 0.5;
     for event in events.iter() {
-        let pos = Vec3::new(event.pos.x, event.pos.y, 0.0);
+        let crater_radius = event.power * event.asteroid_multiplier * crater_scale;
+        if crater_radius <= 0.0 {
+            continue;
+        }
         for (mut asteroid, transform, entity) in asteroids.iter_mut() {
-            let dist = 1.0_f32.max(transform.translation.distance(pos) - asteroid.radius); // Correct for shell
-            if dist < max_dist {
-                let damage = event.power * (max_dist - dist) / max_dist;
-                if asteroid.radius - damage < min_radius {
-                    event_destroy.send(AsteroidDestroyed {asteroid : entity})
-                } else {
-                    asteroid.radius -= damage;
-                }
+            let centre = event.pos - Vec2::new(transform.translation.x, transform.translation.y);
+            // Only touch asteroids in range so the others are not marked as changed
+            if centre.length() > asteroid.radius + crater_radius {
+                continue;
+            }
+            asteroid.crater(centre, crater_radius);
+            let remaining = asteroid.terrain.equivalent_radius();
+            if remaining < min_radius {
+                event_destroy.send(AsteroidDestroyed {asteroid : entity})
+            } else if remaining >= min_split_radius && crater_radius >= split_fraction * remaining {
+                let pieces = if crater_radius >= remaining { 3 } else { 2 };
+                event_split.send(AsteroidSplit { asteroid : entity, impact : centre, pieces : pieces })
+            }
+        }
+    }
+}
+
+fn nearest_fragment(fragments : &Vec<(Entity, Vec2)>, point : Vec2) -> (Entity, Vec2) {
+    let mut nearest = fragments[0];
+    for fragment in fragments.iter() {
+        if fragment.1.distance(point) < nearest.1.distance(point) {
+            nearest = *fragment;
+        }
+    }
+    nearest
+}
+
+// Replace an asteroid with equal fragments flying apart. Bases and mines move to the
+// surface of the nearest fragment, bases take damage for how far they were thrown
+fn split_asteroid(
+    mut commands : Commands,
+    mut events : EventReader<AsteroidSplit>,
+    asteroid_query : Query<(&Asteroid, &AsteroidMotion, &Transform, &Children)>,
+    drawable_query : Query<&AsteroidDrawable>,
+    mut base_query : Query<&mut Base>,
+    mut mine_query : Query<&mut Mine>,
+    mut events_fall : EventWriter<BaseFall>
+) {
+    let split_speed = 30.0;
+    let mut split : Vec<Entity> = Vec::new();
+    for event in events.iter() {
+        if split.contains(&event.asteroid) {
+            continue;
+        }
+        let (asteroid, motion, transform, children) = match asteroid_query.get(event.asteroid) {
+            Ok(found) => found,
+            Err(_) => continue
+        };
+        split.push(event.asteroid);
+        let texture = children.iter().find_map(
+            |child| drawable_query.get(*child).ok()
+        ).map_or_else(
+            default_asteroid_texture,
+            |drawable| drawable.texture.clone()
+        );
+        let pieces = event.pieces.max(2);
+        // Equal shares of the rock left keeps the total mass
+        let radius = (asteroid.terrain.area() / (pieces as f32 * PI)).sqrt();
+        // Far enough apart that neighbouring fragments just touch
+        let spacing = radius / (PI / pieces as f32).sin();
+        // Pieces fly apart across the line of the blow
+        let first_angle = angle_of(event.impact) + 0.5 * PI;
+        let centre = Vec2::new(transform.translation.x, transform.translation.y);
+        let mut fragments : Vec<(Entity, Vec2)> = Vec::new();
+        for index in 0..pieces {
+            let direction = direction_of(first_angle + 2.0 * PI * (index as f32) / (pieces as f32));
+            let offset = direction * spacing;
+            let pos = centre + offset;
+            let (fragment, _) = add_asteroid(&mut commands, pos.x, pos.y, radius, asteroid.density, texture.clone());
+            commands.entity(fragment).insert(AsteroidMotion { velocity : motion.velocity + direction * split_speed });
+            fragments.push((fragment, offset));
+        }
+        for child in children.iter() {
+            if let Ok(mut base) = base_query.get_mut(*child) {
+                let point = direction_of(base.angle) * base.ground;
+                let (fragment, offset) = nearest_fragment(&fragments, point);
+                let angle = angle_of(point - offset);
+                let landing = offset + direction_of(angle) * radius;
+                events_fall.send(BaseFall { base : *child, distance : point.distance(landing) });
+                base.angle = angle;
+                base.ground = radius;
+                commands.entity(*child).insert(Transform {
+                    translation : (direction_of(angle) * (radius - base.offset)).extend(0.0),
+                    rotation : Quat::from_rotation_z(angle),
+                    scale : Vec3::new(1.0, 1.0, 1.0)
+                }).insert(Parent(fragment));
+            } else if let Ok(mut mine) = mine_query.get_mut(*child) {
+                let point = direction_of(mine.angle) * asteroid.height(mine.angle);
+                let (fragment, offset) = nearest_fragment(&fragments, point);
+                let angle = angle_of(point - offset);
+                mine.angle = angle;
+                commands.entity(*child).insert(Transform {
+                    translation : (direction_of(angle) * (radius - mine.offset)).extend(0.0),
+                    rotation : Quat::from_rotation_z(angle),
+                    scale : Vec3::new(1.0, 1.0, 1.0)
+                }).insert(Parent(fragment));
+            } else {
+                commands.entity(*child).despawn_recursive();
             }
         }
+        commands.entity(event.asteroid).despawn();
     }
 }
 
@@ -159,11 +469,22 @@ pub struct AsteroidPlugin;
 impl Plugin for AsteroidPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<AsteroidDestroyed>()
+           .add_event::<AsteroidImpulse>()
+           .add_event::<AsteroidSplit>()
            .add_system_set(
               SystemSet::on_update(AppState::InGame)
                 .with_system(destroy_asteroid.system())
                 .with_system(damage_asteroid.system())
+                .with_system(split_asteroid.system())
                 .with_system(asteroid_changed.system())
+                .with_system(asteroid_gravity_changed.system())
+                .with_system(asteroid_impulse.system())
+              )
+           .add_system_set_to_stage(
+              PhysicsStage,
+              SystemSet::new()
+                .with_system(asteroid_move.system().label(PhysicsLabel::Drift).after(PhysicsLabel::Orbit))
+                .with_system(asteroid_collide.system().label(PhysicsLabel::Separate).after(PhysicsLabel::Drift))
               )
            .add_system_set(
              SystemSet::on_exit(AppState::InGame)
@@ -171,4 +492,16 @@ impl Plugin for AsteroidPlugin {
            );
 
     }
-}
\ No newline at end of file
+}
+
+pub struct AsteroidRenderPlugin;
+
+impl Plugin for AsteroidRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+              SystemSet::on_update(AppState::InGame)
+                .with_system(asteroid_drawable_added.system())
+                .with_system(asteroid_mesh_changed.system())
+              );
+    }
+}
@@ -1,16 +1,30 @@
 use bevy::prelude::*;
 
-use super::turn::TurnStart;
+use std::collections::HashSet;
+
+use super::turn::{TurnStart, TurnEnd, TurnState};
 use super::explosion::Explode;
 use super::app_state::AppState;
 use super::asteroids::Asteroid;
+use super::player::{Player, PlayerSettings, team_mates};
+
+// Width of ground a base stands on
+pub const BASE_WIDTH : f32 = 50.0;
 
 #[derive(Component)]
 pub struct Base {
     pub angle : f32,
     pub offset : f32,
+    // Height of the terrain the base is standing on
+    pub ground : f32,
     pub health : f32,
-    pub health_bar : Entity
+    pub max_health : f32
+}
+
+impl Base {
+    fn health_percent(self : &Self) -> f32 {
+        self.health.clamp(0.0, self.max_health) / self.max_health
+    }
 }
 
 #[derive(Component)]
@@ -22,6 +36,23 @@ pub struct BaseDestroyed {
     pub base : Entity
 }
 
+// The ground under a base was blown away and it dropped down onto what is left
+pub struct BaseFall {
+    pub base : Entity,
+    pub distance : f32
+}
+
+// Spend the turn restoring health to a base
+pub struct BaseRepair {
+    pub base : Entity
+}
+
+// A base being repaired, the turn ends when the repair time runs out
+#[derive(Component)]
+pub struct Repairing {
+    time : f32
+}
+
 #[derive(Component)]
 pub struct BaseActivity {
     pub active : bool
@@ -44,86 +75,119 @@ impl FromWorld for BaseTextures {
     }
 }
 
+// Sprite showing the health of a base
+#[derive(Component)]
+pub struct HealthBar {
+    pub bar : Entity
+}
+
 #[derive(Component)]
 pub struct PercentBar {
     pub val : f32,
     pub size : f32
 }
 
+// Base standing on the surface of an asteroid, it is drawn by base_added
 pub fn add_base(
     commands: &mut Commands, angle : f32, 
-    textures : &BaseTextures,
     asteroid : &(Entity, Asteroid), 
-    player : Entity,
-    player_colour : Color
+    player : Entity
 ) -> Entity {
-    let health_bar = commands.spawn_bundle(SpriteBundle {
-        transform: Transform::from_xyz(0.0, 30.0, 0.0),
-        sprite: Sprite { 
-            custom_size : Some(Vec2::new(45.0, 4.0)),
-            color : player_colour,
-            ..Default::default() 
-        },
-        ..Default::default()
-    }).insert(PercentBar { val : 1.0, size : 45.0}).id();
-
     let offset = -8.5;
-    let radius = asteroid.1.radius - offset;
+    let ground = asteroid.1.footing(angle, BASE_WIDTH);
+    let radius = ground - offset;
     let pos = Vec3::new(-radius * angle.sin(), radius * angle.cos(), 0.0);
 
-    let base = commands.spawn_bundle(SpriteBundle {
-        texture: textures.base.clone(),
-        transform: Transform {
-            rotation : Quat::from_rotation_z(angle),
-            translation : pos,
-            scale : Vec3::new(1.0, 1.0, 1.0)
-        },
-        sprite: Sprite { 
-            custom_size : Some(Vec2::new(50.0, 50.0)),
-            ..Default::default() 
-        },
-        ..Default::default()
-    }).insert(Base{
+    commands.spawn().insert(Transform {
+        rotation : Quat::from_rotation_z(angle),
+        translation : pos,
+        scale : Vec3::new(1.0, 1.0, 1.0)
+    }).insert(GlobalTransform::identity()
+    ).insert(Base{
         angle : angle,
         offset : offset,
+        ground : ground,
         health : 100.0,
-        health_bar : health_bar
+        max_health : 100.0
     }).insert(Parent(asteroid.0)
     ).insert(BaseOwner{entity : player}
     ).insert(BaseActivity{ active : false }
-    ).with_children(|base_builder| {
-        base_builder.spawn_bundle(SpriteBundle {
+    ).id()
+}
+
+// Give new bases their sprite and a health bar in the colour of their owner
+fn base_added(
+    mut commands : Commands,
+    base_query : Query<(Entity, &BaseOwner), Added<Base>>,
+    player_query : Query<&Player>,
+    textures : Res<BaseTextures>
+) {
+    for (base, owner) in base_query.iter() {
+        let player_colour = player_query.get(owner.entity).map_or(Color::WHITE, |player| player.colour);
+        let health_bar = commands.spawn_bundle(SpriteBundle {
+            transform: Transform::from_xyz(0.0, 30.0, 0.0),
             sprite: Sprite { 
-                custom_size : Some(Vec2::new(45.0, 5.0)),
-                color : textures.base_bar_background,
+                custom_size : Some(Vec2::new(45.0, 4.0)),
+                color : player_colour,
                 ..Default::default() 
             },
-            transform: Transform::from_xyz(0.0, 30.0, 0.0),
             ..Default::default()
-        });
-    }).id();
-    commands.entity(base).push_children(&[health_bar]);
-    base
+        }).insert(PercentBar { val : 1.0, size : 45.0}).id();
+        commands.entity(base).insert(Sprite { 
+            custom_size : Some(Vec2::new(BASE_WIDTH, BASE_WIDTH)),
+            ..Default::default() 
+        }).insert(textures.base.clone()
+        ).insert(Visibility::default()
+        ).insert(HealthBar { bar : health_bar }
+        ).with_children(|base_builder| {
+            base_builder.spawn_bundle(SpriteBundle {
+                sprite: Sprite { 
+                    custom_size : Some(Vec2::new(45.0, 5.0)),
+                    color : textures.base_bar_background,
+                    ..Default::default() 
+                },
+                transform: Transform::from_xyz(0.0, 30.0, 0.0),
+                ..Default::default()
+            });
+        }).push_children(&[health_bar]);
+    }
+}
+
+// Keep the health bar in step with the health of its base
+fn base_health_changed(
+    base_query : Query<(&Base, &HealthBar), Changed<Base>>,
+    mut percent_query : Query<&mut PercentBar>
+) {
+    for (base, health_bar) in base_query.iter() {
+        if let Ok(mut bar) = percent_query.get_mut(health_bar.bar) {
+            if bar.val != base.health_percent() {
+                bar.val = base.health_percent();
+            }
+        }
+    }
 }
 
 fn damage_base(
-    mut bases : Query<(&mut Base, &GlobalTransform, Entity)>,
-    mut percent_query : Query<&mut PercentBar>,
+    mut bases : Query<(&mut Base, &GlobalTransform, &BaseOwner, Entity)>,
+    player_query : Query<&Player>,
+    settings : Res<PlayerSettings>,
     mut events : EventReader<Explode>,
     mut event_destroy : EventWriter<BaseDestroyed>
 ) {
     let max_dist = 20.0;
     for event in events.iter() {
         let pos = Vec3::new(event.pos.x, event.pos.y, 0.0);
-        for (mut base, transform, entity) in bases.iter_mut() {
+        for (mut base, transform, owner, entity) in bases.iter_mut() {
+            // Team mates are safe from each other unless friendly fire is on
+            if let Some(source) = event.source {
+                if !settings.friendly_fire && team_mates(&player_query, source, owner.entity) {
+                    continue;
+                }
+            }
             let dist = 1.0_f32.max(transform.translation.distance(pos) - 25.0); // Correct for shell
             if dist < max_dist {
                 let damage = event.power * (max_dist - dist) / max_dist;
                 base.health -= damage;
-                let percent = base.health.max(0.0) / 100.0;
-                if let Ok(mut bar) = percent_query.get_mut(base.health_bar) {
-                    bar.val = percent;
-                }
                 if base.health < 0.0 {
                     event_destroy.send(BaseDestroyed {base : entity})
                 }
@@ -132,11 +196,75 @@ fn damage_base(
     }
 }
 
+// Short drops are harmless, further ones hurt more the further the base falls
+fn base_fall(
+    mut bases : Query<&mut Base>,
+    mut events : EventReader<BaseFall>,
+    mut event_destroy : EventWriter<BaseDestroyed>
+) {
+    let safe_distance = 4.0;
+    let damage_per_unit = 2.0;
+    for event in events.iter() {
+        if let Ok(mut base) = bases.get_mut(event.base) {
+            let damage = (event.distance - safe_distance).max(0.0) * damage_per_unit;
+            if damage <= 0.0 {
+                continue;
+            }
+            base.health -= damage;
+            if base.health < 0.0 {
+                event_destroy.send(BaseDestroyed {base : event.base})
+            }
+        }
+    }
+}
+
+fn base_repair_start(
+    mut commands : Commands,
+    mut events : EventReader<BaseRepair>
+) {
+    for event in events.iter() {
+        commands.entity(event.base).insert(Repairing { time : 1.0 });
+    }
+}
+
+// Heal repairing bases over time up to their maximum health
+fn base_repair_update(
+    mut commands : Commands,
+    mut bases : Query<(Entity, &mut Base, &mut Repairing)>,
+    time : Res<Time>,
+    turn_state : Res<TurnState>,
+    mut events_turn : EventWriter<TurnEnd>
+) {
+    if turn_state.paused() {
+        return;
+    }
+    let repair_rate = 30.0;
+    for (entity, mut base, mut repairing) in bases.iter_mut() {
+        let delta = time.delta_seconds().min(repairing.time);
+        repairing.time -= delta;
+        base.health = (base.health + repair_rate * delta).min(base.max_health);
+        if repairing.time <= 0.0 {
+            commands.entity(entity).remove::<Repairing>();
+            events_turn.send(TurnEnd);
+        }
+    }
+}
+
+// A base destroyed part way through a repair takes the turn with it
 fn destroy_base(
     mut commands : Commands,
     mut events : EventReader<BaseDestroyed>,
+    repairing_query : Query<&Repairing>,
+    mut events_turn : EventWriter<TurnEnd>
 ) {
+    let mut destroyed = HashSet::new();
     for event in events.iter() {
+        if !destroyed.insert(event.base) {
+            continue;
+        }
+        if repairing_query.get(event.base).is_ok() {
+            events_turn.send(TurnEnd);
+        }
         commands.entity(event.base).despawn_recursive();
     }
 }
@@ -195,18 +323,35 @@ pub struct BasePlugin;
 impl Plugin for BasePlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<BaseDestroyed>()
-           .init_resource::<BaseTextures>()
+           .add_event::<BaseRepair>()
+           .add_event::<BaseFall>()
            .add_system_set(
             SystemSet::on_update(AppState::InGame)
-              .with_system(percent_update.system())
               .with_system(base_new_turn.system())
-              .with_system(base_activity_changed.system())
               .with_system(damage_base.system())
+              .with_system(base_fall.system())
               .with_system(destroy_base.system())
+              .with_system(base_repair_start.system())
+              .with_system(base_repair_update.system())
             )
             .add_system_set(
                 SystemSet::on_exit(AppState::InGame)
                   .with_system(teardown_bases.system())
             );
     }
-}
\ No newline at end of file
+}
+
+pub struct BaseRenderPlugin;
+
+impl Plugin for BaseRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BaseTextures>()
+           .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+              .with_system(base_added.system())
+              .with_system(base_health_changed.system())
+              .with_system(percent_update.system())
+              .with_system(base_activity_changed.system())
+            );
+    }
+}
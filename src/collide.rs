@@ -23,17 +23,27 @@ impl Box {
         )
     }
 
-    fn lines(self : &Self) -> [Line; 4] {
+    // Points to test against shapes that can only say whether they contain a point
+    pub fn points(self : &Self) -> [Vec2; 5] {
+        let corners = self.corners();
+        [corners[0], corners[1], corners[2], corners[3], self.centre]
+    }
+
+    fn corners(self : &Self) -> [Vec2; 4] {
         let local_x3 = self.rotation * Vec3::new(self.size.x * 0.5, 0.0, 0.0);
         let local_y3 = self.rotation * Vec3::new(0.0, self.size.y * 0.5, 0.0);
         let local_x = Vec2::new(local_x3.x, local_x3.y);
         let local_y = Vec2::new(local_y3.x, local_y3.y);
-        let points : [Vec2; 4] = [
+        [
             self.centre + local_x + local_y,
             self.centre + local_x - local_y,
             self.centre - local_x - local_y,
             self.centre - local_x + local_y
-        ];
+        ]
+    }
+
+    fn lines(self : &Self) -> [Line; 4] {
+        let points = self.corners();
         [
             Line {start : points[0], end : points[1]},
             Line {start : points[1], end : points[2]},
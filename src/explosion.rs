@@ -2,7 +2,11 @@ use bevy::prelude::*;
 
 pub struct Explode {
     pub pos : Vec2,
-    pub power : f32
+    pub power : f32,
+    // Extra scaling applied to the damage done to asteroids
+    pub asteroid_multiplier : f32,
+    // Player who set it off, if anyone
+    pub source : Option<Entity>
 }
 
 
@@ -0,0 +1,343 @@
+use bevy::prelude::*;
+
+use super::asteroids::Asteroid;
+use super::map::{spawn_map, load_map, MapChoice, MapLayout};
+use super::map_gen::{generate_map, choose_seed, MapSettings, MapSeed};
+use super::physics::{PhysicsStage, PhysicsLabel};
+use super::base::{BaseOwner, BaseRepair};
+use super::weapon::{Weapon, WeaponType, Launch, WeaponExplode, WeaponBore, WeaponYank, Boring, Impact};
+use super::inventory::Inventory;
+use super::turn::{TurnState, TurnStart, TurnEnd, TurnFiring, TurnPhase, TurnTimeout, TurnTimerSettings, TimeoutAction};
+use super::player::{setup_players, PlayerOrder, PlayerSettings};
+use super::mine::MineLand;
+use super::app_state::AppState;
+use super::ai::AiTurn;
+
+// The rules of a game, with nothing drawn or read from the keyboard so it can be run
+// without a window. The client plugin puts the game on screen and turns key presses
+// into turn actions
+
+// Events
+
+// What the player whose turn it is has chosen to do, at the angle and power in TurnState
+pub enum TurnAction {
+    Fire,
+    Repair,
+    // Do nothing and let the next player go
+    Skip
+}
+
+// Resources
+
+// Map of the last game, kept so a match can be restarted on the same map
+#[derive(Default)]
+struct LastGame {
+    layout : Option<MapLayout>,
+    replay : bool
+}
+
+// Components
+
+#[derive(Component)]
+struct Background;
+
+// Systems
+
+fn setup_game(
+    mut commands: Commands,
+    mut player_order: ResMut<PlayerOrder>,
+    player_settings : Res<PlayerSettings>,
+    map_choice : Res<MapChoice>,
+    map_settings : Res<MapSettings>,
+    mut map_seed : ResMut<MapSeed>,
+    mut last_game : ResMut<LastGame>,
+    mut events : EventWriter<TurnStart>
+) {
+    // Players
+    let players = setup_players(&mut commands, &mut player_order, &player_settings);
+    // The same map again when restarting, otherwise a map from a file falling back to
+    // a generated one if it can not be loaded. A map with more slots than players is only
+    // played if the slots can be shared out evenly
+    let replay = last_game.replay;
+    last_game.replay = false;
+    let loaded = if replay {
+        last_game.layout.clone()
+    } else {
+        match &*map_choice {
+            MapChoice::File(path) => match load_map(path) {
+                Ok(layout) if layout.players < players.len() || layout.players % players.len() != 0 => {
+                    eprintln!(
+                        "{}: {} player slots can not be shared between {} players, generating a map",
+                        path, layout.players, players.len()
+                    );
+                    None
+                },
+                Ok(layout) => Some(layout),
+                Err(error) => {
+                    eprintln!("{}", error);
+                    None
+                }
+            },
+            MapChoice::Generated => None
+        }
+    };
+    let layout = match loaded {
+        // A replayed map keeps the seed it was generated from
+        Some(layout) if replay => {
+            map_seed.set_changed();
+            layout
+        },
+        Some(layout) => {
+            map_seed.generated = false;
+            layout
+        },
+        None => {
+            map_seed.seed = choose_seed(&map_settings);
+            map_seed.generated = true;
+            generate_map(&map_settings, map_seed.seed, players.len())
+        }
+    };
+    // Asteroids and bases
+    let first_base = spawn_map(&mut commands, &layout, &player_order.order);
+    if let Some(base) = first_base {
+        events.send(TurnStart{new_base : base});
+    }
+    commands.insert_resource(layout);
+}
+
+fn turn_action_system(
+    mut actions : EventReader<TurnAction>,
+    mut turn_state : ResMut<TurnState>,
+    owner_query : Query<&BaseOwner>,
+    mut inventory_query : Query<&mut Inventory>,
+    mut launch_events: EventWriter<Launch>,
+    mut repair_events: EventWriter<BaseRepair>,
+    mut turn_events : EventWriter<TurnFiring>,
+    mut end_events : EventWriter<TurnEnd>
+) {
+    for action in actions.iter() {
+        if turn_state.phase != TurnPhase::Aiming {
+            continue;
+        }
+        let base = match turn_state.active_base {
+            Some(base) => base,
+            None => continue
+        };
+        match action {
+            TurnAction::Fire => {
+                // Use up a weapon from the inventory of the player who owns the base
+                let owner = match owner_query.get(base) {
+                    Ok(owner) => owner.entity,
+                    Err(_) => continue
+                };
+                if let Ok(mut inventory) = inventory_query.get_mut(owner) {
+                    let weapon_type = inventory.selected;
+                    if inventory.take(weapon_type) {
+                        launch_events.send(Launch{
+                            angle : turn_state.firing_angle,
+                            offset : 12.0,
+                            thrust : turn_state.power,
+                            parent : base,
+                            weapon_type : weapon_type
+                        });
+                        turn_state.phase = TurnPhase::Firing;
+                        turn_events.send(TurnFiring);
+                    }
+                }
+            },
+            // Spend the turn repairing the active base instead of firing
+            TurnAction::Repair => {
+                repair_events.send(BaseRepair { base : base });
+                turn_state.phase = TurnPhase::Firing;
+                turn_events.send(TurnFiring);
+            },
+            // The phase changes here rather than on the TurnFiring event, so the next turn
+            // starting straight away can not be overwritten by it
+            TurnAction::Skip => {
+                turn_state.phase = TurnPhase::Firing;
+                turn_events.send(TurnFiring);
+                end_events.send(TurnEnd);
+            }
+        }
+        // Only one action a turn
+        break;
+    }
+}
+
+// Act for a player who ran out of time, skipping instead of firing if they have nothing
+// left of the weapon they have selected
+fn turn_timeout_system(
+    mut timeouts : EventReader<TurnTimeout>,
+    settings : Res<TurnTimerSettings>,
+    turn_state : Res<TurnState>,
+    owner_query : Query<&BaseOwner>,
+    inventory_query : Query<&Inventory>,
+    mut actions : EventWriter<TurnAction>
+) {
+    for _ in timeouts.iter() {
+        let can_fire = turn_state.active_base.and_then(
+            |base| owner_query.get(base).ok()
+        ).and_then(
+            |owner| inventory_query.get(owner.entity).ok()
+        ).map_or(false, |inventory| inventory.count(inventory.selected) > 0);
+        if settings.on_timeout == TimeoutAction::Fire && can_fire {
+            actions.send(TurnAction::Fire);
+        } else {
+            actions.send(TurnAction::Skip);
+        }
+    }
+}
+
+// Weapons which hit an asteroid stop until the hit has been dealt with, so they end up
+// in the same place however many steps run before then
+fn rocket_asteroid_collide_system(
+    mut commands : Commands,
+    rocket_query : Query<(Entity, &Weapon, &Transform), (Without<Boring>, Without<Impact>)>,
+    asteroid_query : Query<(Entity, &Asteroid, &Transform)>,
+    mut events: EventWriter<WeaponExplode>,
+    mut events_mine: EventWriter<MineLand>,
+    mut events_bore: EventWriter<WeaponBore>,
+    mut events_yank: EventWriter<WeaponYank>
+) {
+    for (entity, rocket, rocket_transform) in rocket_query.iter() {
+        for (asteroid_entity, asteroid, asteroid_transform) in asteroid_query.iter() {
+            let bound = rocket.bound(rocket_transform);
+            // Cheap test against the furthest the surface reaches before checking the terrain
+            if !bound.collide(asteroid.bound(asteroid_transform)) {
+                continue;
+            }
+            let hit = bound.points().iter().any(
+                |point| asteroid.contains(asteroid_transform, *point)
+            );
+            if hit {
+                commands.entity(entity).insert(Impact);
+                match rocket.weapon_type {
+                    WeaponType::Mine => events_mine.send(MineLand { mine : entity, asteroid : asteroid_entity }),
+                    WeaponType::AsteroidMine => events_bore.send(WeaponBore { entity : entity, asteroid : asteroid_entity }),
+                    WeaponType::AsteroidYank => events_yank.send(WeaponYank { entity : entity, asteroid : asteroid_entity }),
+                    _ => events.send(WeaponExplode { entity : entity })
+                }
+                break;
+            }
+        }
+    }
+}
+
+fn setup_cameras(
+    mut commands: Commands
+) {
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+// Background sprite for the map being played, once it has been set up
+fn background_added(
+    mut commands: Commands,
+    layout : Option<Res<MapLayout>>,
+    asset_server: Res<AssetServer>
+) {
+    let layout = match layout {
+        Some(layout) if layout.is_added() => layout,
+        _ => return
+    };
+    commands.spawn_bundle(SpriteBundle {
+        texture: asset_server.load(layout.background.as_str()),
+        transform: Transform::from_scale(Vec3::new(1.5, 1.5, 1.5)),
+        ..Default::default()
+    }).insert(Background);
+}
+
+fn firing_system(
+    key_input: Res<Input<KeyCode>>,
+    ai_turn : Res<AiTurn>,
+    mut actions : EventWriter<TurnAction>
+) {
+    if ai_turn.active() {
+        return;
+    }
+    if key_input.just_pressed(KeyCode::Space) {
+        actions.send(TurnAction::Fire);
+    } else if key_input.just_pressed(KeyCode::R) {
+        actions.send(TurnAction::Repair);
+    }
+}
+
+fn restart_game(
+    mut state : ResMut<State<AppState>>,
+    mut last_game : ResMut<LastGame>
+) {
+    last_game.replay = true;
+    state.set(AppState::InGame).unwrap();
+}
+
+fn teardown_game(
+    mut commands : Commands,
+    layout : Option<Res<MapLayout>>,
+    mut last_game : ResMut<LastGame>
+) {
+    last_game.layout = layout.map(|layout| (*layout).clone());
+    commands.remove_resource::<MapLayout>();
+}
+
+fn teardown_ingame(
+    mut commands : Commands,
+    background_query : Query<Entity, With<Background>>
+) {
+    for background in background_query.iter() {
+        commands.entity(background).despawn_recursive();
+    }
+}
+
+// Plugins
+
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TurnAction>()
+           .init_resource::<LastGame>()
+           .add_system_set(
+               SystemSet::on_enter(AppState::InGame)
+                 .with_system(setup_game.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(AppState::InGame)
+                 .with_system(turn_action_system.system())
+                 .with_system(turn_timeout_system.system())
+           )
+           .add_system_set_to_stage(
+               PhysicsStage,
+               SystemSet::new()
+                 .with_system(rocket_asteroid_collide_system.system().after(PhysicsLabel::Separate))
+           )
+           .add_system_set(
+               SystemSet::on_exit(AppState::InGame)
+                 .with_system(teardown_game.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(AppState::Restarting)
+                 .with_system(restart_game.system())
+           );
+    }
+}
+
+// Cameras, the background and the keyboard controls of a game
+pub struct GameClientPlugin;
+
+impl Plugin for GameClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+               SystemSet::on_enter(AppState::InGame)
+                 .with_system(setup_cameras.system())
+           )
+           .add_system_set(
+               SystemSet::on_update(AppState::InGame)
+                 .with_system(background_added.system())
+                 .with_system(firing_system.system())
+           )
+           .add_system_set(
+               SystemSet::on_exit(AppState::InGame)
+                 .with_system(teardown_ingame.system())
+           );
+    }
+}
@@ -0,0 +1,268 @@
+use bevy::prelude::*;
+
+use super::weapon::{Weapon, ActiveWeapons};
+use super::turn::{TurnStart, TurnEnd};
+use super::app_state::AppState;
+use super::physics::{PhysicsStage, PhysicsLabel};
+
+// Components
+
+// Anything which pulls on weapons in flight
+#[derive(Component)]
+pub struct GravitySource {
+    pub mass : f32
+}
+
+// A temporary gravity source left behind by a weapon, it lasts a number of turns
+// and destroys any weapon which crosses its horizon
+#[derive(Component)]
+pub struct GravityWell {
+    pub turns : u32,
+    pub horizon : f32,
+    pub well_type : GravityWellType
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GravityWellType {
+    BlackHole,
+    WhiteHole
+}
+
+// Events
+pub struct SpawnGravityWell {
+    pub pos : Vec2,
+    pub well_type : GravityWellType
+}
+
+// Resources
+pub struct GravityWellSettings {
+    pub black_hole_turns : u32,
+    pub black_hole_mass : f32,
+    pub black_hole_horizon : f32,
+    pub white_hole_turns : u32,
+    // Negative mass pushes weapons away
+    pub white_hole_mass : f32,
+    // Size of the visible field around a white hole
+    pub white_hole_radius : f32
+}
+
+impl Default for GravityWellSettings {
+    fn default() -> Self {
+        GravityWellSettings {
+            black_hole_turns : 3,
+            black_hole_mass : 1.0e6,
+            black_hole_horizon : 15.0,
+            white_hole_turns : 4,
+            white_hole_mass : -6.0e5,
+            white_hole_radius : 80.0
+        }
+    }
+}
+
+pub struct GravityWellTextures {
+    white_hole : Handle<Image>
+}
+
+impl FromWorld for GravityWellTextures {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        GravityWellTextures {
+            white_hole : asset_server.load("images/smoke_particle.png")
+        }
+    }
+}
+
+// Change in velocity over a time step from one source, anything closer than 1 is
+// treated as the source itself and ignored
+fn pull(mass : f32, source_pos : Vec2, position : Vec2, delta_seconds : f32) -> Vec2 {
+    let delta = position - source_pos;
+    if delta.length() > 1.0 {
+        let mass = delta_seconds * mass;
+        let dist_sq = delta.length_squared();
+        -(mass / dist_sq) * delta.normalize()
+    } else {
+        Vec2::ZERO
+    }
+}
+
+// Copy of a gravity source, moving bodies are pulled by the others in the same way as
+// weapons when predicting paths
+#[derive(Clone)]
+pub struct GravityBody {
+    pub mass : f32,
+    pub pos : Vec2,
+    pub velocity : Vec2,
+    pub moving : bool
+}
+
+// The gravity sources as they are now, sources are all at the top level so their
+// transform is their position even part way through a frame
+pub fn gravity_bodies(source_query : &Query<(&GravitySource, &Transform)>) -> Vec<GravityBody> {
+    source_query.iter().map(
+        |(source, transform)| GravityBody {
+            mass : source.mass,
+            pos : Vec2::new(transform.translation.x, transform.translation.y),
+            velocity : Vec2::ZERO,
+            moving : false
+        }
+    ).collect()
+}
+
+// Change in velocity over a time step at a position
+pub fn body_gravity(bodies : &Vec<GravityBody>, position : Vec2, delta_seconds : f32) -> Vec2 {
+    bodies.iter().map(
+        |body| pull(body.mass, body.pos, position, delta_seconds)
+    ).fold(Vec2::ZERO, |total, force| total + force)
+}
+
+// Move the moving bodies on by a time step
+pub fn step_bodies(bodies : &mut Vec<GravityBody>, delta_seconds : f32) {
+    let changes : Vec<Vec2> = bodies.iter().map(
+        |body| if body.moving { body_gravity(bodies, body.pos, delta_seconds) } else { Vec2::ZERO }
+    ).collect();
+    for (body, change) in bodies.iter_mut().zip(changes) {
+        if body.moving {
+            body.velocity += change;
+            body.pos += body.velocity * delta_seconds;
+        }
+    }
+}
+
+// Systems
+
+fn spawn_gravity_well(
+    mut commands : Commands,
+    mut events : EventReader<SpawnGravityWell>,
+    settings : Res<GravityWellSettings>
+) {
+    for event in events.iter() {
+        let (mass, well) = match event.well_type {
+            GravityWellType::BlackHole => (
+                settings.black_hole_mass, 
+                GravityWell { 
+                    turns : settings.black_hole_turns, 
+                    horizon : settings.black_hole_horizon, 
+                    well_type : event.well_type 
+                }
+            ),
+            GravityWellType::WhiteHole => (
+                settings.white_hole_mass, 
+                GravityWell { 
+                    turns : settings.white_hole_turns, 
+                    horizon : 0.0, 
+                    well_type : event.well_type 
+                }
+            )
+        };
+        commands.spawn().insert(GravitySource { 
+            mass : mass
+        }).insert(well
+        ).insert(Transform::from_xyz(event.pos.x, event.pos.y, 0.0)
+        ).insert(GlobalTransform::from_xyz(event.pos.x, event.pos.y, 0.0));
+    }
+}
+
+// Black holes have no body, white holes show the field pushing weapons away
+fn gravity_well_added(
+    mut commands : Commands,
+    well_query : Query<(Entity, &GravityWell), Added<GravityWell>>,
+    settings : Res<GravityWellSettings>,
+    textures : Res<GravityWellTextures>
+) {
+    for (entity, well) in well_query.iter() {
+        if well.well_type == GravityWellType::WhiteHole {
+            let size = 2.0 * settings.white_hole_radius;
+            commands.entity(entity).with_children(|parent| {
+                parent.spawn_bundle(SpriteBundle {
+                    texture : textures.white_hole.clone(),
+                    sprite : Sprite {
+                        custom_size : Some(Vec2::new(size, size)),
+                        color : Color::rgba(0.7, 0.85, 1.0, 0.35),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+            });
+        }
+    }
+}
+
+fn gravity_well_turn(
+    mut commands : Commands,
+    mut events : EventReader<TurnStart>,
+    mut well_query : Query<(Entity, &mut GravityWell)>
+) {
+    for _ in events.iter() {
+        for (entity, mut well) in well_query.iter_mut() {
+            if well.turns <= 1 {
+                commands.entity(entity).despawn_recursive();
+            } else {
+                well.turns -= 1;
+            }
+        }
+    }
+}
+
+// Weapons which cross the horizon of a well are lost without exploding, checked
+// after every move so a fast weapon can not step over the horizon
+fn gravity_well_swallow(
+    mut commands : Commands,
+    well_query : Query<(&GravityWell, &Transform)>,
+    weapon_query : Query<(Entity, &Transform), With<Weapon>>,
+    mut active_weapons : ResMut<ActiveWeapons>,
+    mut events_turn : EventWriter<TurnEnd>
+) {
+    for (well, well_transform) in well_query.iter() {
+        for (weapon, weapon_transform) in weapon_query.iter() {
+            let dist = weapon_transform.translation.distance(well_transform.translation);
+            if dist < well.horizon && active_weapons.retire(weapon, &mut events_turn) {
+                commands.entity(weapon).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn teardown_gravity_wells(
+    mut commands : Commands,
+    well_query : Query<Entity, With<GravityWell>>
+) {
+    for well in well_query.iter() {
+        commands.entity(well).despawn_recursive();
+    }
+}
+
+// Plugins
+pub struct GravityPlugin;
+
+impl Plugin for GravityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnGravityWell>()
+           .init_resource::<GravityWellSettings>()
+           .add_system_set(
+             SystemSet::on_update(AppState::InGame)
+               .with_system(spawn_gravity_well.system())
+               .with_system(gravity_well_turn.system())
+           )
+           .add_system_set_to_stage(
+             PhysicsStage,
+             SystemSet::new()
+               .with_system(gravity_well_swallow.system().after(PhysicsLabel::Move))
+           )
+           .add_system_set(
+             SystemSet::on_exit(AppState::InGame)
+               .with_system(teardown_gravity_wells.system())
+           );
+    }
+}
+
+pub struct GravityRenderPlugin;
+
+impl Plugin for GravityRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GravityWellTextures>()
+           .add_system_set(
+             SystemSet::on_update(AppState::InGame)
+               .with_system(gravity_well_added.system())
+           );
+    }
+}
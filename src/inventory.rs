@@ -0,0 +1,350 @@
+use bevy::prelude::*;
+
+use std::collections::HashMap;
+
+use super::weapon::WeaponType;
+use super::turn::{TurnPhase, TurnState, TurnStart};
+use super::player::PlayerOrder;
+use super::app_state::AppState;
+use super::ai::AiTurn;
+
+// Components
+
+// Weapons a player has left to fire and the one they currently have selected
+#[derive(Clone, Component)]
+pub struct Inventory {
+    counts : HashMap<WeaponType, u32>,
+    pub selected : WeaponType
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        let counts = [
+            (WeaponType::Rocket, 20),
+            (WeaponType::MultiRocket, 3),
+            (WeaponType::HomingRocket, 2),
+            (WeaponType::Mine, 3),
+            (WeaponType::AsteroidMine, 2),
+            (WeaponType::AsteroidYank, 2),
+            (WeaponType::BlackHole, 1),
+            (WeaponType::WhiteHole, 1)
+        ].into_iter().collect();
+        Inventory {
+            counts : counts,
+            selected : WeaponType::Rocket
+        }
+    }
+}
+
+impl Inventory {
+    pub fn count(self : &Self, weapon_type : WeaponType) -> u32 {
+        *self.counts.get(&weapon_type).unwrap_or(&0)
+    }
+
+    // Use up one of a weapon, returns false if there are none left
+    pub fn take(self : &mut Self, weapon_type : WeaponType) -> bool {
+        match self.counts.get_mut(&weapon_type) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            },
+            _ => false
+        }
+    }
+
+    // Move the selection forwards or backwards through all the weapon slots
+    pub fn cycle(self : &mut Self, step : i32) {
+        let len = WeaponType::ALL.len() as i32;
+        let index = WeaponType::ALL.iter().position(
+            |weapon_type| *weapon_type == self.selected
+        ).unwrap_or(0) as i32;
+        self.selected = WeaponType::ALL[(index + step).rem_euclid(len) as usize];
+    }
+}
+
+#[derive(Component)]
+struct SelectedWeaponUI;
+
+// The strip showing every weapon slot of the current player
+#[derive(Component)]
+struct InventoryStripUI;
+
+#[derive(Component)]
+struct InventorySlotUI {
+    weapon_type : WeaponType
+}
+
+#[derive(Component)]
+struct InventoryIconUI {
+    weapon_type : WeaponType
+}
+
+#[derive(Component)]
+struct InventoryAmountUI {
+    weapon_type : WeaponType
+}
+
+// Resources
+struct InventoryStripColours {
+    slot : Color,
+    slot_selected : Color,
+    icon : Color,
+    icon_empty : Color
+}
+
+impl FromWorld for InventoryStripColours {
+    fn from_world(_: &mut World) -> Self {
+        InventoryStripColours {
+            slot : Color::rgba(0.4, 0.4, 0.4, 0.8),
+            slot_selected : Color::rgba(0.9, 0.8, 0.2, 0.9),
+            icon : Color::WHITE,
+            icon_empty : Color::rgba(0.3, 0.3, 0.3, 0.6)
+        }
+    }
+}
+
+pub struct InventoryIcons {
+    icons : HashMap<WeaponType, Handle<Image>>,
+    no_item : Handle<Image>
+}
+
+impl InventoryIcons {
+    pub fn icon(self : &Self, weapon_type : WeaponType) -> Handle<Image> {
+        self.icons.get(&weapon_type).unwrap_or(&self.no_item).clone()
+    }
+}
+
+impl FromWorld for InventoryIcons {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let icons = WeaponType::ALL.iter().map(|weapon_type| {
+            let path = match weapon_type {
+                WeaponType::Rocket => "images/missile_icon_full.png",
+                WeaponType::MultiRocket => "images/missile_multi_icon_full.png",
+                WeaponType::HomingRocket => "images/missile_target_icon_full.png",
+                WeaponType::Mine => "images/mines_icon_full.png",
+                WeaponType::AsteroidMine => "images/asteroid_mine_icon_full.png",
+                WeaponType::AsteroidYank => "images/asteroid_yank_icon_full.png",
+                WeaponType::BlackHole => "images/black_hole_icon_full.png",
+                WeaponType::WhiteHole => "images/white_hole_icon_full.png"
+            };
+            (*weapon_type, asset_server.load(path))
+        }).collect();
+        InventoryIcons {
+            icons : icons,
+            no_item : asset_server.load("images/no_item_icon_full.png")
+        }
+    }
+}
+
+// Systems
+
+fn inventory_select(
+    key_input: Res<Input<KeyCode>>,
+    turn_state : Res<TurnState>,
+    ai_turn : Res<AiTurn>,
+    player_order : Res<PlayerOrder>,
+    mut inventory_query : Query<&mut Inventory>
+) {
+    if turn_state.phase != TurnPhase::Aiming || ai_turn.active() {
+        return;
+    }
+    let step = if key_input.just_pressed(KeyCode::E) {
+        1
+    } else if key_input.just_pressed(KeyCode::Q) {
+        -1
+    } else {
+        return;
+    };
+    if let Some(player) = player_order.order.get(player_order.current) {
+        if let Ok(mut inventory) = inventory_query.get_mut(*player) {
+            inventory.cycle(step);
+        }
+    }
+}
+
+fn setup_inventory_ui(
+    mut commands : Commands,
+    icons : Res<InventoryIcons>,
+    colours : Res<InventoryStripColours>,
+    asset_server : Res<AssetServer>
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    // Strip of weapon slots to the left of the player panel
+    commands.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Auto, Val::Auto),
+            flex_direction : FlexDirection::Row,
+            position_type: PositionType::Absolute,
+            position: Rect {
+                right: Val::Px(240.0),
+                top: Val::Px(20.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        color: Color::NONE.into(),
+        ..Default::default()
+    })
+    .with_children(|parent| {
+        for weapon_type in WeaponType::ALL {
+            parent.spawn_bundle(NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Px(56.0), Val::Px(56.0)),
+                    margin: Rect::all(Val::Px(2.0)),
+                    padding: Rect::all(Val::Px(4.0)),
+                    ..Default::default()
+                },
+                color: colours.slot.into(),
+                ..Default::default()
+            })
+            .insert(InventorySlotUI { weapon_type : weapon_type })
+            .with_children(|slot| {
+                slot.spawn_bundle(ImageBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(48.0), Val::Px(48.0)),
+                        ..Default::default()
+                    },
+                    image: UiImage(icons.icon(weapon_type)),
+                    color: colours.icon.into(),
+                    ..Default::default()
+                }).insert(InventoryIconUI { weapon_type : weapon_type });
+                // Amount overlaid in the bottom right of the icon
+                slot.spawn_bundle(TextBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        position: Rect {
+                            right: Val::Px(4.0),
+                            bottom: Val::Px(2.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        "0",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 18.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                }).insert(InventoryAmountUI { weapon_type : weapon_type });
+            });
+        }
+    }).insert(InventoryStripUI);
+
+    commands.spawn_bundle(ImageBundle {
+        style: Style {
+            size: Size::new(Val::Px(80.0), Val::Px(80.0)),
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(20.0),
+                top: Val::Px(20.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        image: UiImage(icons.no_item.clone()),
+        ..Default::default()
+    }).insert(SelectedWeaponUI);
+}
+
+// Show the selected weapon of the current player, or the empty slot icon if they have run out
+fn inventory_ui_update(
+    mut ui_query : Query<&mut UiImage, With<SelectedWeaponUI>>,
+    inventory_query : Query<&Inventory>,
+    player_order : Res<PlayerOrder>,
+    icons : Res<InventoryIcons>
+) {
+    let inventory = player_order.order.get(player_order.current).and_then(
+        |player| inventory_query.get(*player).ok()
+    );
+    let icon = match inventory {
+        Some(inventory) if inventory.count(inventory.selected) > 0 => icons.icon(inventory.selected),
+        _ => icons.no_item.clone()
+    };
+    for mut image in ui_query.iter_mut() {
+        if image.0 != icon {
+            image.0 = icon.clone();
+        }
+    }
+}
+
+// Refresh the strip when the turn passes to another player or their inventory changes
+fn inventory_strip_update(
+    mut events : EventReader<TurnStart>,
+    changed_query : Query<Entity, Changed<Inventory>>,
+    inventory_query : Query<&Inventory>,
+    player_order : Res<PlayerOrder>,
+    colours : Res<InventoryStripColours>,
+    mut query : QuerySet<(
+        QueryState<(&InventorySlotUI, &mut UiColor)>,
+        QueryState<(&InventoryIconUI, &mut UiColor)>,
+        QueryState<(&InventoryAmountUI, &mut Text)>
+    )>
+) {
+    let turn_started = events.iter().count() > 0;
+    if !turn_started && changed_query.iter().next().is_none() {
+        return;
+    }
+    let inventory = match player_order.order.get(player_order.current).and_then(
+        |player| inventory_query.get(*player).ok()
+    ) {
+        Some(inventory) => inventory,
+        None => return
+    };
+    for (slot, mut colour) in query.q0().iter_mut() {
+        *colour = if slot.weapon_type == inventory.selected {
+            colours.slot_selected.into()
+        } else {
+            colours.slot.into()
+        };
+    }
+    for (icon, mut colour) in query.q1().iter_mut() {
+        *colour = if inventory.count(icon.weapon_type) > 0 {
+            colours.icon.into()
+        } else {
+            colours.icon_empty.into()
+        };
+    }
+    for (amount, mut text) in query.q2().iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            section.value = inventory.count(amount.weapon_type).to_string();
+        }
+    }
+}
+
+fn teardown_inventory_ui(
+    mut commands : Commands,
+    ui_query : Query<Entity, Or<(With<SelectedWeaponUI>, With<InventoryStripUI>)>>
+) {
+    for ui in ui_query.iter() {
+        commands.entity(ui).despawn_recursive();
+    }
+}
+
+// Plugins
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InventoryIcons>()
+           .init_resource::<InventoryStripColours>()
+           .add_system_set(
+             SystemSet::on_enter(AppState::InGame)
+               .with_system(setup_inventory_ui.system())
+           )
+           .add_system_set(
+             SystemSet::on_update(AppState::InGame)
+               .with_system(inventory_select.system())
+               .with_system(inventory_ui_update.system())
+               .with_system(inventory_strip_update.system())
+           )
+           .add_system_set(
+             SystemSet::on_exit(AppState::InGame)
+               .with_system(teardown_inventory_ui.system())
+           );
+    }
+}
\ No newline at end of file
@@ -0,0 +1,112 @@
+#![feature(exact_size_is_empty)]
+
+use bevy::prelude::*;
+use bevy::app::PluginGroupBuilder;
+use bevy::transform::TransformPlugin;
+
+pub mod collide;
+pub mod base;
+pub mod asteroids;
+pub mod weapon;
+pub mod turn;
+pub mod aiming;
+pub mod player;
+pub mod explosion;
+pub mod mine;
+pub mod gravity;
+pub mod orbit;
+pub mod physics;
+pub mod terrain;
+pub mod inventory;
+pub mod map;
+pub mod map_gen;
+pub mod map_editor;
+pub mod app_state;
+pub mod main_menu;
+pub mod lobby;
+pub mod victory_menu;
+pub mod pause_menu;
+pub mod game;
+pub mod ai;
+
+use asteroids::{AsteroidPlugin, AsteroidRenderPlugin};
+use map::MapPlugin;
+use map_gen::{MapGenPlugin, MapSeedUiPlugin};
+use map_editor::MapEditorPlugin;
+use gravity::{GravityPlugin, GravityRenderPlugin};
+use orbit::OrbitPlugin;
+use physics::PhysicsPlugin;
+use base::{BasePlugin, BaseRenderPlugin};
+use weapon::{WeaponPlugin, WeaponRenderPlugin};
+use inventory::InventoryPlugin;
+use turn::{TurnPlugin, TurnTimerUiPlugin};
+use aiming::AimingPlugin;
+use player::{PlayerPlugin, PlayerUiPlugin};
+use explosion::ExplosionPlugin;
+use mine::{MinePlugin, MineRenderPlugin};
+use game::{GamePlugin, GameClientPlugin};
+use app_state::AppState;
+use main_menu::MainMenuPlugin;
+use lobby::LobbyPlugin;
+use victory_menu::VictoryMenuPlugin;
+use pause_menu::PauseMenuPlugin;
+use ai::AiPlugin;
+
+// The rules of the game, these need nothing more than MinimalPlugins and TransformPlugin
+// so games can be played without a window
+pub struct SimulationPlugins;
+
+impl PluginGroup for SimulationPlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        // Physics first as other plugins put systems in its stage
+        group.add(PhysicsPlugin)
+             .add(GamePlugin)
+             .add(PlayerPlugin)
+             .add(AsteroidPlugin)
+             .add(WeaponPlugin)
+             .add(TurnPlugin)
+             .add(ExplosionPlugin)
+             .add(MinePlugin)
+             .add(GravityPlugin)
+             .add(OrbitPlugin)
+             .add(MapPlugin)
+             .add(MapGenPlugin)
+             .add(BasePlugin)
+             .add(AiPlugin);
+    }
+}
+
+// Menus, drawing and controls, added on top of the simulation with DefaultPlugins
+pub struct ClientPlugins;
+
+impl PluginGroup for ClientPlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group.add(MainMenuPlugin)
+             .add(LobbyPlugin)
+             .add(GameClientPlugin)
+             .add(PlayerUiPlugin)
+             .add(TurnTimerUiPlugin)
+             .add(AsteroidRenderPlugin)
+             .add(WeaponRenderPlugin)
+             .add(AimingPlugin)
+             .add(MineRenderPlugin)
+             .add(GravityRenderPlugin)
+             .add(InventoryPlugin)
+             .add(MapSeedUiPlugin)
+             .add(MapEditorPlugin)
+             .add(BaseRenderPlugin)
+             .add(VictoryMenuPlugin)
+             .add(PauseMenuPlugin);
+    }
+}
+
+// An app running only the simulation, starting at the main menu. Move to
+// AppState::InGame to start a game and call update to step it
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+       .add_plugin(TransformPlugin)
+       .add_state(AppState::MainMenu)
+       .add_plugins(SimulationPlugins);
+    app
+}
@@ -0,0 +1,337 @@
+use bevy::prelude::*;
+
+use super::app_state::AppState;
+use super::player::{PlayerSettings, MIN_PLAYERS, MAX_PLAYERS};
+
+// Screen off the main menu for choosing who plays. Click a name to type a new one,
+// click the colour next to it to change colour and the button after it to hand the
+// player to the computer. With teams on each player also has a team button to cycle
+// through the teams
+
+const MAX_NAME_LENGTH : usize = 16;
+
+struct ButtonMaterials {
+    normal: Color,
+    hovered: Color,
+    pressed: Color,
+    selected: Color
+}
+
+impl FromWorld for ButtonMaterials {
+    fn from_world(_: &mut World) -> Self {
+        ButtonMaterials {
+            normal: Color::rgb(0.15, 0.15, 0.15),
+            hovered: Color::rgb(0.25, 0.25, 0.25),
+            pressed: Color::rgb(0.35, 0.75, 0.35),
+            selected: Color::rgb(0.3, 0.3, 0.45)
+        }
+    }
+}
+
+#[derive(Default)]
+struct LobbyData {
+    root_entity: Option<Entity>
+}
+
+// Player whose name is being typed
+#[derive(Default)]
+struct LobbyEdit {
+    selected : Option<usize>
+}
+
+#[derive(Component, Clone, Copy)]
+enum LobbyButton {
+    Colour(usize),
+    Name(usize),
+    Remove(usize),
+    Controller(usize),
+    Team(usize),
+    Add,
+    Teams,
+    FriendlyFire,
+    Done
+}
+
+fn spawn_lobby(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    button_materials: &ButtonMaterials,
+    settings: &PlayerSettings,
+    edit: &LobbyEdit
+) -> Entity {
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 40.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    let button_style = |width : f32| Style {
+        size: Size::new(Val::Px(width), Val::Px(55.0)),
+        margin: Rect::all(Val::Px(5.0)),
+        // horizontally center child text
+        justify_content: JustifyContent::Center,
+        // vertically center child text
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
+    let text = |value : String| TextBundle {
+        text: Text::with_section(value, text_style.clone(), Default::default()),
+        ..Default::default()
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                // center everything
+                margin: Rect::all(Val::Auto),
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(text("Players".to_string()));
+            for (index, player) in settings.players.iter().enumerate() {
+                let selected = edit.selected == Some(index);
+                parent.spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|row| {
+                    row.spawn_bundle(ButtonBundle {
+                        style: button_style(55.0),
+                        color: player.colour.into(),
+                        ..Default::default()
+                    })
+                    .insert(LobbyButton::Colour(index));
+                    row.spawn_bundle(ButtonBundle {
+                        style: button_style(400.0),
+                        color: if selected { button_materials.selected } else { button_materials.normal }.into(),
+                        ..Default::default()
+                    })
+                    .insert(LobbyButton::Name(index))
+                    .with_children(|button| {
+                        let cursor = if selected { "_" } else { "" };
+                        button.spawn_bundle(text(format!("{}{}", player.name, cursor)));
+                    });
+                    row.spawn_bundle(ButtonBundle {
+                        style: button_style(220.0),
+                        color: button_materials.normal.into(),
+                        ..Default::default()
+                    })
+                    .insert(LobbyButton::Controller(index))
+                    .with_children(|button| {
+                        button.spawn_bundle(text(player.controller.label().to_string()));
+                    });
+                    if settings.teams {
+                        row.spawn_bundle(ButtonBundle {
+                            style: button_style(150.0),
+                            color: button_materials.normal.into(),
+                            ..Default::default()
+                        })
+                        .insert(LobbyButton::Team(index))
+                        .with_children(|button| {
+                            button.spawn_bundle(text(format!("Team {}", player.team + 1)));
+                        });
+                    }
+                    if settings.players.len() > MIN_PLAYERS {
+                        row.spawn_bundle(ButtonBundle {
+                            style: button_style(55.0),
+                            color: button_materials.normal.into(),
+                            ..Default::default()
+                        })
+                        .insert(LobbyButton::Remove(index))
+                        .with_children(|button| {
+                            button.spawn_bundle(text("X".to_string()));
+                        });
+                    }
+                });
+            }
+            if settings.players.len() < MAX_PLAYERS {
+                parent.spawn_bundle(ButtonBundle {
+                    style: button_style(250.0),
+                    color: button_materials.normal.into(),
+                    ..Default::default()
+                })
+                .insert(LobbyButton::Add)
+                .with_children(|button| {
+                    button.spawn_bundle(text("Add player".to_string()));
+                });
+            }
+            let on_off = |on : bool| if on { "On" } else { "Off" };
+            parent.spawn_bundle(ButtonBundle {
+                style: button_style(250.0),
+                color: button_materials.normal.into(),
+                ..Default::default()
+            })
+            .insert(LobbyButton::Teams)
+            .with_children(|button| {
+                button.spawn_bundle(text(format!("Teams: {}", on_off(settings.teams))));
+            });
+            if settings.teams {
+                parent.spawn_bundle(ButtonBundle {
+                    style: button_style(350.0),
+                    color: button_materials.normal.into(),
+                    ..Default::default()
+                })
+                .insert(LobbyButton::FriendlyFire)
+                .with_children(|button| {
+                    button.spawn_bundle(text(format!("Friendly fire: {}", on_off(settings.friendly_fire))));
+                });
+            }
+            parent.spawn_bundle(ButtonBundle {
+                style: button_style(250.0),
+                color: button_materials.normal.into(),
+                ..Default::default()
+            })
+            .insert(LobbyButton::Done)
+            .with_children(|button| {
+                button.spawn_bundle(text("Done".to_string()));
+            });
+        })
+        .id()
+}
+
+// Build the screen on arriving and rebuild it whenever the players or the name being
+// edited change
+fn lobby_refresh(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<ButtonMaterials>,
+    settings: Res<PlayerSettings>,
+    edit: Res<LobbyEdit>,
+    mut lobby_data: ResMut<LobbyData>
+) {
+    if let Some(root_entity) = lobby_data.root_entity {
+        if !settings.is_changed() && !edit.is_changed() {
+            return;
+        }
+        commands.entity(root_entity).despawn_recursive();
+    }
+    lobby_data.root_entity = Some(
+        spawn_lobby(&mut commands, &asset_server, &button_materials, &settings, &edit)
+    );
+}
+
+fn lobby_menu(
+    mut state: ResMut<State<AppState>>,
+    button_materials: Res<ButtonMaterials>,
+    mut settings: ResMut<PlayerSettings>,
+    mut edit: ResMut<LobbyEdit>,
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor, &LobbyButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
+        // Colour buttons always show the colour they pick
+        let show_state = !matches!(button, LobbyButton::Colour(_));
+        match *interaction {
+            Interaction::Clicked => {
+                if show_state {
+                    *color = button_materials.pressed.into();
+                }
+                match *button {
+                    LobbyButton::Colour(index) => settings.next_colour(index),
+                    LobbyButton::Name(index) => edit.selected = Some(index),
+                    LobbyButton::Remove(index) => {
+                        if settings.remove(index) {
+                            edit.selected = None;
+                        }
+                    },
+                    LobbyButton::Controller(index) => settings.next_controller(index),
+                    LobbyButton::Team(index) => settings.next_team(index),
+                    LobbyButton::Teams => settings.teams = !settings.teams,
+                    LobbyButton::FriendlyFire => settings.friendly_fire = !settings.friendly_fire,
+                    LobbyButton::Add => {
+                        if settings.add() {
+                            edit.selected = Some(settings.players.len() - 1);
+                        }
+                    },
+                    LobbyButton::Done => state.set(AppState::MainMenu).unwrap()
+                }
+                // The screen is rebuilt so the other buttons are about to go
+                break;
+            }
+            Interaction::Hovered if show_state => {
+                *color = button_materials.hovered.into();
+            }
+            Interaction::None if show_state => {
+                *color = match *button {
+                    LobbyButton::Name(index) if edit.selected == Some(index) => button_materials.selected,
+                    _ => button_materials.normal
+                }.into();
+            }
+            _ => {}
+        }
+    }
+}
+
+// Typing goes into the selected name
+fn lobby_typing(
+    mut chars: EventReader<ReceivedCharacter>,
+    key_input: Res<Input<KeyCode>>,
+    mut settings: ResMut<PlayerSettings>,
+    mut edit: ResMut<LobbyEdit>
+) {
+    let index = match edit.selected {
+        Some(index) if index < settings.players.len() => index,
+        _ => return
+    };
+    if key_input.just_pressed(KeyCode::Return) || key_input.just_pressed(KeyCode::Escape) {
+        edit.selected = None;
+        return;
+    }
+    if key_input.just_pressed(KeyCode::Back) {
+        settings.players[index].name.pop();
+    }
+    for event in chars.iter() {
+        let name = &mut settings.players[index].name;
+        if !event.char.is_control() && name.chars().count() < MAX_NAME_LENGTH {
+            name.push(event.char);
+        }
+    }
+}
+
+fn cleanup_lobby(
+    mut commands: Commands,
+    mut lobby_data: ResMut<LobbyData>,
+    mut edit: ResMut<LobbyEdit>,
+    mut settings: ResMut<PlayerSettings>
+) {
+    if let Some(root_entity) = lobby_data.root_entity.take() {
+        commands.entity(root_entity).despawn_recursive();
+    }
+    edit.selected = None;
+    // Nobody plays without a name
+    for (index, player) in settings.players.iter_mut().enumerate() {
+        if player.name.trim().is_empty() {
+            player.name = format!("Player {}", index + 1);
+        }
+    }
+}
+
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ButtonMaterials>()
+           .init_resource::<LobbyData>()
+           .init_resource::<LobbyEdit>()
+           .add_system_set(
+               SystemSet::on_update(AppState::Lobby)
+                 .with_system(lobby_menu.system())
+                 .with_system(lobby_typing.system())
+                 .with_system(lobby_refresh.system())
+                )
+           .add_system_set(
+               SystemSet::on_exit(AppState::Lobby)
+                 .with_system(cleanup_lobby.system())
+               );
+    }
+}
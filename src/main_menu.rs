@@ -1,6 +1,10 @@
 use bevy::prelude::*;
 
 use super::app_state::AppState;
+use super::map::{MapChoice, available_maps};
+use super::orbit::OrbitSettings;
+use super::turn::TurnTimerSettings;
+use super::player::PlayerSettings;
 
 // Based on bevy example menu code
 
@@ -24,40 +28,162 @@ struct MenuData {
     button_entity: Entity,
 }
 
+#[derive(Component)]
+enum MenuButton {
+    Play,
+    Players,
+    Map,
+    Orbits,
+    TurnTimer,
+    Editor
+}
+
+#[derive(Component)]
+struct MapButtonText;
+
+#[derive(Component)]
+struct OrbitButtonText;
+
+#[derive(Component)]
+struct TurnTimerButtonText;
+
+fn orbit_label(settings : &OrbitSettings) -> String {
+    format!("Orbits: {}", if settings.enabled { "On" } else { "Off" })
+}
+
 fn setup_menu(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     button_materials: Res<ButtonMaterials>,
+    map_choice: Res<MapChoice>,
+    orbit_settings: Res<OrbitSettings>,
+    timer_settings: Res<TurnTimerSettings>,
+    player_settings: Res<PlayerSettings>,
 ) {
     // ui camera
     commands.spawn_bundle(UiCameraBundle::default());
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 40.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    let button_style = |width : f32| Style {
+        size: Size::new(Val::Px(width), Val::Px(65.0)),
+        margin: Rect::all(Val::Px(10.0)),
+        // horizontally center child text
+        justify_content: JustifyContent::Center,
+        // vertically center child text
+        align_items: AlignItems::Center,
+        ..Default::default()
+    };
     let button_entity = commands
-        .spawn_bundle(ButtonBundle {
+        .spawn_bundle(NodeBundle {
             style: Style {
-                size: Size::new(Val::Px(150.0), Val::Px(65.0)),
-                // center button
+                // center buttons
                 margin: Rect::all(Val::Auto),
-                // horizontally center child text
-                justify_content: JustifyContent::Center,
-                // vertically center child text
+                flex_direction: FlexDirection::ColumnReverse,
                 align_items: AlignItems::Center,
                 ..Default::default()
             },
-            color: button_materials.normal.into(),
+            color: Color::NONE.into(),
             ..Default::default()
         })
         .with_children(|parent| {
-            parent.spawn_bundle(TextBundle {
-                text: Text::with_section(
-                    "Play",
-                    TextStyle {
-                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                        font_size: 40.0,
-                        color: Color::rgb(0.9, 0.9, 0.9),
-                    },
-                    Default::default(),
-                ),
+            parent.spawn_bundle(ButtonBundle {
+                style: button_style(150.0),
+                color: button_materials.normal.into(),
+                ..Default::default()
+            })
+            .insert(MenuButton::Play)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "Play",
+                        text_style.clone(),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            });
+            parent.spawn_bundle(ButtonBundle {
+                style: button_style(250.0),
+                color: button_materials.normal.into(),
                 ..Default::default()
+            })
+            .insert(MenuButton::Players)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        format!("Players: {}", player_settings.players.len()),
+                        text_style.clone(),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            });
+            parent.spawn_bundle(ButtonBundle {
+                style: button_style(350.0),
+                color: button_materials.normal.into(),
+                ..Default::default()
+            })
+            .insert(MenuButton::Map)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        format!("Map: {}", map_choice.label()),
+                        text_style.clone(),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                }).insert(MapButtonText);
+            });
+            parent.spawn_bundle(ButtonBundle {
+                style: button_style(250.0),
+                color: button_materials.normal.into(),
+                ..Default::default()
+            })
+            .insert(MenuButton::Orbits)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        orbit_label(&orbit_settings),
+                        text_style.clone(),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                }).insert(OrbitButtonText);
+            });
+            parent.spawn_bundle(ButtonBundle {
+                style: button_style(350.0),
+                color: button_materials.normal.into(),
+                ..Default::default()
+            })
+            .insert(MenuButton::TurnTimer)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        timer_settings.label(),
+                        text_style.clone(),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                }).insert(TurnTimerButtonText);
+            });
+            parent.spawn_bundle(ButtonBundle {
+                style: button_style(250.0),
+                color: button_materials.normal.into(),
+                ..Default::default()
+            })
+            .insert(MenuButton::Editor)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "Map Editor",
+                        text_style.clone(),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
             });
         })
         .id();
@@ -67,16 +193,26 @@ fn setup_menu(
 fn menu(
     mut state: ResMut<State<AppState>>,
     button_materials: Res<ButtonMaterials>,
+    mut map_choice: ResMut<MapChoice>,
+    mut orbit_settings: ResMut<OrbitSettings>,
+    mut timer_settings: ResMut<TurnTimerSettings>,
     mut interaction_query: Query<
-        (&Interaction, &mut UiColor),
+        (&Interaction, &mut UiColor, &MenuButton),
         (Changed<Interaction>, With<Button>),
     >,
 ) {
-    for (interaction, mut color) in interaction_query.iter_mut() {
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
         match *interaction {
             Interaction::Clicked => {
                 *color = button_materials.pressed.into();
-                state.set(AppState::InGame).unwrap();
+                match button {
+                    MenuButton::Play => state.set(AppState::InGame).unwrap(),
+                    MenuButton::Players => state.set(AppState::Lobby).unwrap(),
+                    MenuButton::Map => *map_choice = map_choice.next(&available_maps()),
+                    MenuButton::Orbits => orbit_settings.enabled = !orbit_settings.enabled,
+                    MenuButton::TurnTimer => timer_settings.next_limit(),
+                    MenuButton::Editor => state.set(AppState::MapEditor).unwrap()
+                }
             }
             Interaction::Hovered => {
                 *color = button_materials.hovered.into();
@@ -88,6 +224,48 @@ fn menu(
     }
 }
 
+fn map_button_text(
+    map_choice: Res<MapChoice>,
+    mut text_query: Query<&mut Text, With<MapButtonText>>
+) {
+    if !map_choice.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            section.value = format!("Map: {}", map_choice.label());
+        }
+    }
+}
+
+fn orbit_button_text(
+    orbit_settings: Res<OrbitSettings>,
+    mut text_query: Query<&mut Text, With<OrbitButtonText>>
+) {
+    if !orbit_settings.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            section.value = orbit_label(&orbit_settings);
+        }
+    }
+}
+
+fn turn_timer_button_text(
+    timer_settings: Res<TurnTimerSettings>,
+    mut text_query: Query<&mut Text, With<TurnTimerButtonText>>
+) {
+    if !timer_settings.is_changed() {
+        return;
+    }
+    for mut text in text_query.iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            section.value = timer_settings.label();
+        }
+    }
+}
+
 fn cleanup_menu(mut commands: Commands, menu_data: Res<MenuData>) {
     commands.entity(menu_data.button_entity).despawn_recursive();
 }
@@ -104,6 +282,9 @@ impl Plugin for MainMenuPlugin {
            .add_system_set(
                SystemSet::on_update(AppState::MainMenu)
                  .with_system(menu.system())
+                 .with_system(map_button_text.system())
+                 .with_system(orbit_button_text.system())
+                 .with_system(turn_timer_button_text.system())
                 )
            .add_system_set(
                SystemSet::on_exit(AppState::MainMenu)
@@ -0,0 +1,381 @@
+use bevy::prelude::*;
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+use super::asteroids::add_asteroid;
+use super::base::add_base;
+
+// Description of the asteroids and bases making up a map, this is also the
+// format of the map files in assets/maps
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BaseLayout {
+    pub angle : f32,
+    // Index of the player slot which owns the base
+    pub player : usize
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct AsteroidLayout {
+    pub x : f32,
+    pub y : f32,
+    pub radius : f32,
+    // Relative to ordinary rock, denser asteroids pull harder and are harder to move
+    #[serde(default = "default_density")]
+    pub density : f32,
+    #[serde(default = "default_asteroid_texture")]
+    pub texture : String,
+    #[serde(default)]
+    pub bases : Vec<BaseLayout>
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MapLayout {
+    #[serde(default = "default_background")]
+    pub background : String,
+    // Number of player slots bases are handed out to
+    pub players : usize,
+    pub asteroids : Vec<AsteroidLayout>
+}
+
+pub fn default_asteroid_texture() -> String {
+    "images/pallas_asteroid_alpha.png".to_string()
+}
+
+pub fn default_density() -> f32 {
+    1.0
+}
+
+pub fn default_background() -> String {
+    "images/starfield.png".to_string()
+}
+
+impl AsteroidLayout {
+    pub fn pos(self : &Self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+
+    // Base position in world space for a base at an angle on the asteroid
+    pub fn base_position(self : &Self, angle : f32) -> Vec2 {
+        let radius = self.radius + 8.5;
+        self.pos() + Vec2::new(-radius * angle.sin(), radius * angle.cos())
+    }
+}
+
+// Which map the next game is played on
+#[derive(Clone, PartialEq, Debug)]
+pub enum MapChoice {
+    Generated,
+    File(String)
+}
+
+impl Default for MapChoice {
+    fn default() -> Self {
+        MapChoice::Generated
+    }
+}
+
+impl MapChoice {
+    pub fn label(self : &Self) -> String {
+        match self {
+            MapChoice::Generated => "Random".to_string(),
+            MapChoice::File(path) => std::path::Path::new(path).file_stem().map(
+                |stem| stem.to_string_lossy().to_string()
+            ).unwrap_or(path.clone())
+        }
+    }
+
+    // The choice after this one, going through the random map then each map file
+    pub fn next(self : &Self, maps : &Vec<String>) -> MapChoice {
+        let index = match self {
+            MapChoice::Generated => 0,
+            MapChoice::File(path) => maps.iter().position(|map| map == path).map_or(0, |index| index + 1)
+        };
+        match maps.get(index) {
+            Some(map) => MapChoice::File(map.clone()),
+            None => MapChoice::Generated
+        }
+    }
+}
+
+pub const MAP_DIRECTORY : &str = "assets/maps";
+
+// All the map files which can be picked from the menu
+pub fn available_maps() -> Vec<String> {
+    let mut maps : Vec<String> = std::fs::read_dir(MAP_DIRECTORY).map(
+        |entries| entries.filter_map(
+            |entry| entry.ok().map(|entry| entry.path())
+        ).filter(
+            |path| path.extension().map_or(false, |ext| ext == "ron")
+        ).map(
+            |path| path.to_string_lossy().to_string()
+        ).collect()
+    ).unwrap_or_default();
+    maps.sort();
+    maps
+}
+
+// Errors
+
+#[derive(Debug)]
+pub enum MapError {
+    Io { path : String, message : String },
+    // The file could not be parsed, field is the path to the value being read
+    Parse { path : String, field : String, line : usize, message : String },
+    // The file parsed but describes a map which can not be played, line is where the
+    // field is set or the closest entry enclosing it
+    Invalid { path : String, field : String, line : usize, message : String }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(self : &Self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Io { path, message } =>
+                write!(f, "{}: could not read map: {}", path, message),
+            MapError::Parse { path, field, line, message } =>
+                write!(f, "{}:{}: error in field `{}`: {}", path, line, field, message),
+            MapError::Invalid { path, field, line, message } =>
+                write!(f, "{}:{}: invalid field `{}`: {}", path, line, field, message)
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+pub fn parse_map(path : &str, text : &str) -> Result<MapLayout, MapError> {
+    let parse_error = |field : String, line : usize, message : String| MapError::Parse {
+        path : path.to_string(), field : field, line : line, message : message
+    };
+    let mut deserializer = ron::de::Deserializer::from_str(text).map_err(
+        |error| parse_error(".".to_string(), error.position.line, error.code.to_string())
+    )?;
+    let layout : MapLayout = match serde_path_to_error::deserialize(&mut deserializer) {
+        Ok(layout) => layout,
+        Err(error) => {
+            // Errors from serde about missing or mistyped fields carry no position,
+            // so use where the parser got to instead
+            let position = error.inner().position;
+            let line = if position.line > 0 {
+                position.line
+            } else {
+                let offset = text.len() - deserializer.remainder().len();
+                text[..offset].matches('\n').count() + 1
+            };
+            return Err(parse_error(error.path().to_string(), line, error.inner().code.to_string()));
+        }
+    };
+    deserializer.end().map_err(
+        |error| parse_error(".".to_string(), error.position.line, error.code.to_string())
+    )?;
+    validate_map(path, text, &layout)?;
+    Ok(layout)
+}
+
+fn validate_map(path : &str, text : &str, layout : &MapLayout) -> Result<(), MapError> {
+    let invalid = |field : String, message : String| MapError::Invalid {
+        path : path.to_string(), line : field_line(text, &field), field : field, message : message
+    };
+    if layout.players == 0 {
+        return Err(invalid("players".to_string(), "a map needs at least one player slot".to_string()));
+    }
+    for (index, asteroid) in layout.asteroids.iter().enumerate() {
+        if asteroid.radius <= 0.0 {
+            return Err(invalid(
+                format!("asteroids[{}].radius", index),
+                format!("radius must be positive, found {}", asteroid.radius)
+            ));
+        }
+        if asteroid.density <= 0.0 {
+            return Err(invalid(
+                format!("asteroids[{}].density", index),
+                format!("density must be positive, found {}", asteroid.density)
+            ));
+        }
+        for (base_index, base) in asteroid.bases.iter().enumerate() {
+            if base.player >= layout.players {
+                return Err(invalid(
+                    format!("asteroids[{}].bases[{}].player", index, base_index),
+                    format!("slot {} but the map only has {} player slots", base.player, layout.players)
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Line of each token in a map file, strings and comments are dropped as no field
+// names can be in them. Brackets and separators are tokens of their own
+fn map_tokens(text : &str) -> Vec<(String, usize)> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '"' => {
+                let mut escaped = false;
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\n' => line += 1,
+                        '"' if !escaped => break,
+                        _ => {}
+                    }
+                    escaped = c == '\\' && !escaped;
+                }
+                tokens.push(("\"".to_string(), line));
+            },
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.peek().map_or(false, |c| *c != '\n') {
+                    chars.next();
+                }
+            },
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                while let Some(c) = chars.next() {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+            },
+            '(' | ')' | '[' | ']' | '{' | '}' | ',' | ':' => tokens.push((c.to_string(), line)),
+            c if c.is_whitespace() => {},
+            c => {
+                let mut word = c.to_string();
+                while let Some(next) = chars.peek() {
+                    if next.is_whitespace() || "()[]{},:\"/".contains(*next) {
+                        break;
+                    }
+                    word.push(*next);
+                    chars.next();
+                }
+                tokens.push((word, line));
+            }
+        }
+    }
+    tokens
+}
+
+// Index of the comma or closing bracket after the value starting at the given token
+fn skip_value(tokens : &Vec<(String, usize)>, mut index : usize) -> usize {
+    let mut depth = 0;
+    while let Some((token, _)) = tokens.get(index) {
+        match token.as_str() {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" if depth == 0 => break,
+            ")" | "]" | "}" => depth -= 1,
+            "," if depth == 0 => break,
+            _ => {}
+        }
+        index += 1;
+    }
+    index
+}
+
+// Line in a map file where a field such as `asteroids[2].bases[0].player` is set. Fields
+// are only known by path once the file is read, so this follows the path through the text
+// again. If the field itself can not be found the line of the entry holding it is used
+fn field_line(text : &str, field : &str) -> usize {
+    let tokens = map_tokens(text);
+    let token = |index : usize| tokens.get(index).map_or("", |(token, _)| token.as_str());
+    let mut line = 1;
+    let mut index = 0;
+    for segment in field.split('.') {
+        let (name, list_index) = match segment.split_once('[') {
+            Some((name, rest)) => (name, rest.trim_end_matches(']').parse::<usize>().ok()),
+            None => (segment, None)
+        };
+        // Step inside the struct, which may be named
+        if token(index) != "(" && token(index + 1) == "(" {
+            index += 1;
+        }
+        if token(index) != "(" {
+            return line;
+        }
+        index += 1;
+        loop {
+            if token(index) == name && token(index + 1) == ":" {
+                line = tokens[index].1;
+                index += 2;
+                break;
+            }
+            index = skip_value(&tokens, index);
+            if token(index) != "," {
+                return line;
+            }
+            index += 1;
+        }
+        if let Some(list_index) = list_index {
+            if token(index) != "[" {
+                return line;
+            }
+            index += 1;
+            for _ in 0..list_index {
+                index = skip_value(&tokens, index);
+                if token(index) != "," {
+                    return line;
+                }
+                index += 1;
+            }
+            match tokens.get(index) {
+                Some((token, element_line)) if !"])".contains(token.as_str()) => line = *element_line,
+                _ => return line
+            }
+        }
+    }
+    line
+}
+
+pub fn load_map(path : &str) -> Result<MapLayout, MapError> {
+    let text = std::fs::read_to_string(path).map_err(
+        |error| MapError::Io { path : path.to_string(), message : error.to_string() }
+    )?;
+    parse_map(path, &text)
+}
+
+// Build the world for a map, returns the first base of the first player to start the game
+// with, or of whoever has one if the first player has none. Slots beyond the number of
+// players are handed round them in turn
+pub fn spawn_map(
+    commands : &mut Commands,
+    layout : &MapLayout,
+    players : &Vec<Entity>
+) -> Option<Entity> {
+    let mut first_base = None;
+    let mut any_base = None;
+    for asteroid_layout in layout.asteroids.iter() {
+        let asteroid = add_asteroid(
+            commands,
+            asteroid_layout.x,
+            asteroid_layout.y,
+            asteroid_layout.radius,
+            asteroid_layout.density,
+            asteroid_layout.texture.clone()
+        );
+        for base_layout in asteroid_layout.bases.iter() {
+            if let Some(player) = players.get(base_layout.player % players.len().max(1)) {
+                let base = add_base(commands, base_layout.angle, &asteroid, *player);
+                if base_layout.player % players.len() == 0 && first_base.is_none() {
+                    first_base = Some(base);
+                }
+                any_base = any_base.or(Some(base));
+            }
+        }
+    }
+    first_base.or(any_base)
+}
+
+// Plugins
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapChoice>();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,425 @@
+use bevy::prelude::*;
+use bevy::input::mouse::MouseWheel;
+
+use super::app_state::AppState;
+use super::asteroids::asteroid_mass;
+use super::gravity::{GravitySource, gravity_bodies};
+use super::aiming::calculate_position;
+use super::player::PLAYER_COLOURS;
+use super::map::{MapLayout, AsteroidLayout, BaseLayout, MapChoice, MAP_DIRECTORY, load_map, default_asteroid_texture, default_background, default_density};
+
+const SLOT_KEYS : [KeyCode; 8] = [
+    KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8
+];
+
+// Components
+
+// Everything spawned by the editor, removed when leaving it
+#[derive(Component)]
+struct EditorEntity;
+
+// Sprites drawing the layout, rebuilt whenever the layout changes
+#[derive(Component)]
+struct EditorShape;
+
+#[derive(Component)]
+struct EditorTracer {
+    delay : f32
+}
+
+#[derive(Component)]
+struct EditorHelpText;
+
+// Resources
+
+pub struct MapEditor {
+    layout : MapLayout,
+    slot : usize,
+    // Asteroid being dragged and where it was grabbed relative to its centre
+    dragging : Option<(usize, Vec2)>,
+    // Base the gravity preview is fired from
+    preview : Option<(usize, usize)>,
+    // File this visit to the editor has saved to, later saves go to the same file
+    saved_path : Option<String>,
+    dirty : bool,
+    message : String
+}
+
+impl Default for MapEditor {
+    fn default() -> Self {
+        MapEditor {
+            layout : MapLayout {
+                background : default_background(),
+                players : 2,
+                asteroids : Vec::new()
+            },
+            slot : 0,
+            dragging : None,
+            preview : None,
+            saved_path : None,
+            dirty : true,
+            message : String::new()
+        }
+    }
+}
+
+impl MapEditor {
+    // Asteroid under a point
+    fn asteroid_at(self : &Self, pos : Vec2) -> Option<usize> {
+        self.layout.asteroids.iter().position(
+            |asteroid| asteroid.pos().distance(pos) < asteroid.radius
+        )
+    }
+
+    // Asteroid with its surface close to a point
+    fn surface_at(self : &Self, pos : Vec2) -> Option<usize> {
+        self.layout.asteroids.iter().position(
+            |asteroid| (asteroid.pos().distance(pos) - asteroid.radius).abs() < 25.0
+        )
+    }
+}
+
+pub struct EditorTextures {
+    base : Handle<Image>,
+    tracer : Handle<Image>
+}
+
+impl FromWorld for EditorTextures {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        EditorTextures {
+            base : asset_server.load("images/base.png"),
+            tracer : asset_server.load("images/missile_target_2.png")
+        }
+    }
+}
+
+// First custom map file name not already taken, so saving never replaces another map
+fn unused_map_path() -> String {
+    (1..).map(
+        |index| format!("{}/custom_{}.ron", MAP_DIRECTORY, index)
+    ).find(
+        |path| !std::path::Path::new(path).exists()
+    ).unwrap()
+}
+
+fn cursor_world_position(windows : &Windows) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    // The editor camera sits at the origin at unit scale
+    Some(cursor - Vec2::new(window.width(), window.height()) * 0.5)
+}
+
+// Systems
+
+fn setup_editor(
+    mut commands : Commands,
+    mut editor : ResMut<MapEditor>,
+    map_choice : Res<MapChoice>,
+    textures : Res<EditorTextures>,
+    asset_server : Res<AssetServer>
+) {
+    *editor = MapEditor::default();
+    // Start from the chosen map if there is one
+    if let MapChoice::File(path) = &*map_choice {
+        match load_map(path) {
+            Ok(layout) => {
+                editor.layout = layout;
+                editor.message = format!("Editing {}", path);
+            },
+            Err(error) => editor.message = error.to_string()
+        }
+    }
+    commands.spawn_bundle(OrthographicCameraBundle::new_2d()).insert(EditorEntity);
+    for i in 1..11 {
+        commands.spawn_bundle(SpriteBundle {
+            texture: textures.tracer.clone(),
+            sprite: Sprite { custom_size : Some(Vec2::new(6.0, 6.0)), ..Default::default() },
+            visibility: Visibility { is_visible : false },
+            ..Default::default()
+        }).insert(EditorTracer { delay : (i as f32) * 0.3 }).insert(EditorEntity);
+    }
+    commands.spawn_bundle(TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(20.0),
+                bottom: Val::Px(20.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::rgb(0.8, 0.8, 0.8),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    }).insert(EditorHelpText).insert(EditorEntity);
+}
+
+fn editor_mouse(
+    mut editor : ResMut<MapEditor>,
+    windows : Res<Windows>,
+    mouse_input : Res<Input<MouseButton>>,
+    mut wheel_events : EventReader<MouseWheel>
+) {
+    let cursor = match cursor_world_position(&windows) {
+        Some(cursor) => cursor,
+        None => return
+    };
+    // Left button adds or drags asteroids
+    if mouse_input.just_pressed(MouseButton::Left) {
+        match editor.asteroid_at(cursor) {
+            Some(index) => {
+                let grab = cursor - editor.layout.asteroids[index].pos();
+                editor.dragging = Some((index, grab));
+            },
+            None => {
+                editor.layout.asteroids.push(AsteroidLayout {
+                    x : cursor.x,
+                    y : cursor.y,
+                    radius : 50.0,
+                    density : default_density(),
+                    texture : default_asteroid_texture(),
+                    bases : Vec::new()
+                });
+                editor.dirty = true;
+            }
+        }
+    }
+    if mouse_input.just_released(MouseButton::Left) {
+        editor.dragging = None;
+    }
+    if let Some((index, grab)) = editor.dragging {
+        if let Some(asteroid) = editor.layout.asteroids.get_mut(index) {
+            let pos = cursor - grab;
+            if asteroid.pos() != pos {
+                asteroid.x = pos.x;
+                asteroid.y = pos.y;
+                editor.dirty = true;
+            }
+        }
+    }
+    // Right button drops a base on the surface for the current slot, or removes one already there
+    if mouse_input.just_pressed(MouseButton::Right) {
+        if let Some(index) = editor.surface_at(cursor) {
+            let slot = editor.slot;
+            let asteroid = &mut editor.layout.asteroids[index];
+            let existing = asteroid.bases.iter().position(
+                |base| asteroid.base_position(base.angle).distance(cursor) < 25.0
+            );
+            match existing {
+                Some(base_index) => {
+                    asteroid.bases.remove(base_index);
+                    editor.preview = None;
+                },
+                None => {
+                    let delta = cursor - asteroid.pos();
+                    let angle = (-delta.x).atan2(delta.y);
+                    asteroid.bases.push(BaseLayout { angle : angle, player : slot });
+                    let base_index = asteroid.bases.len() - 1;
+                    editor.preview = Some((index, base_index));
+                }
+            }
+            editor.dirty = true;
+        }
+    }
+    // Wheel resizes the asteroid under the cursor
+    let scroll : f32 = wheel_events.iter().map(|event| event.y).sum();
+    if scroll != 0.0 {
+        if let Some(index) = editor.asteroid_at(cursor) {
+            let asteroid = &mut editor.layout.asteroids[index];
+            asteroid.radius = (asteroid.radius + 5.0 * scroll.signum()).clamp(15.0, 150.0);
+            editor.dirty = true;
+        }
+    }
+}
+
+fn editor_keys(
+    mut editor : ResMut<MapEditor>,
+    mut map_choice : ResMut<MapChoice>,
+    mut state : ResMut<State<AppState>>,
+    windows : Res<Windows>,
+    key_input : Res<Input<KeyCode>>
+) {
+    for (slot, key) in SLOT_KEYS.iter().enumerate() {
+        if key_input.just_pressed(*key) {
+            editor.slot = slot;
+            editor.layout.players = editor.layout.players.max(slot + 1);
+            editor.dirty = true;
+        }
+    }
+    if key_input.just_pressed(KeyCode::Delete) || key_input.just_pressed(KeyCode::Back) {
+        if let Some(index) = cursor_world_position(&windows).and_then(|cursor| editor.asteroid_at(cursor)) {
+            editor.layout.asteroids.remove(index);
+            editor.dragging = None;
+            editor.preview = None;
+            editor.dirty = true;
+        }
+    }
+    // Brackets make the asteroid under the cursor lighter or heavier
+    let density_step = if key_input.just_pressed(KeyCode::LBracket) {
+        -0.25
+    } else if key_input.just_pressed(KeyCode::RBracket) {
+        0.25
+    } else {
+        0.0
+    };
+    if density_step != 0.0 {
+        if let Some(index) = cursor_world_position(&windows).and_then(|cursor| editor.asteroid_at(cursor)) {
+            let asteroid = &mut editor.layout.asteroids[index];
+            asteroid.density = (asteroid.density + density_step).clamp(0.25, 4.0);
+            let density = asteroid.density;
+            editor.message = format!("Density {:.2}", density);
+            editor.dirty = true;
+        }
+    }
+    if key_input.just_pressed(KeyCode::S) {
+        let path = editor.saved_path.clone().unwrap_or_else(unused_map_path);
+        let saved = ron::ser::to_string_pretty(&editor.layout, ron::ser::PrettyConfig::new()).map_err(
+            |error| error.to_string()
+        ).and_then(
+            |text| std::fs::write(&path, text).map_err(|error| error.to_string())
+        );
+        editor.message = match saved {
+            Ok(_) => {
+                *map_choice = MapChoice::File(path.clone());
+                editor.saved_path = Some(path.clone());
+                format!("Saved to {}", path)
+            },
+            Err(error) => format!("Could not save {}: {}", path, error)
+        };
+        editor.dirty = true;
+    }
+    if key_input.just_pressed(KeyCode::Escape) {
+        state.set(AppState::MainMenu).unwrap();
+    }
+}
+
+// Redraw the layout after it has been edited
+fn editor_redraw(
+    mut commands : Commands,
+    mut editor : ResMut<MapEditor>,
+    shape_query : Query<Entity, With<EditorShape>>,
+    mut text_query : Query<&mut Text, With<EditorHelpText>>,
+    textures : Res<EditorTextures>,
+    asset_server : Res<AssetServer>
+) {
+    if !editor.dirty {
+        return;
+    }
+    editor.dirty = false;
+    for shape in shape_query.iter() {
+        commands.entity(shape).despawn_recursive();
+    }
+    for asteroid in editor.layout.asteroids.iter() {
+        let pos = asteroid.pos();
+        commands.spawn_bundle(SpriteBundle {
+            texture: asset_server.load(asteroid.texture.as_str()),
+            transform: Transform::from_xyz(pos.x, pos.y, 0.0),
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(2.0 * asteroid.radius, 2.0 * asteroid.radius)),
+                ..Default::default()
+            },
+            ..Default::default()
+        }).insert(GravitySource {
+            mass : asteroid_mass(asteroid.radius, asteroid.density)
+        }).insert(EditorShape).insert(EditorEntity);
+        for base in asteroid.bases.iter() {
+            let base_pos = asteroid.base_position(base.angle);
+            commands.spawn_bundle(SpriteBundle {
+                texture: textures.base.clone(),
+                transform: Transform {
+                    translation : Vec3::new(base_pos.x, base_pos.y, 0.1),
+                    rotation : Quat::from_rotation_z(base.angle),
+                    scale : Vec3::new(1.0, 1.0, 1.0)
+                },
+                sprite: Sprite {
+                    custom_size : Some(Vec2::new(50.0, 50.0)),
+                    color : PLAYER_COLOURS[base.player % PLAYER_COLOURS.len()],
+                    ..Default::default()
+                },
+                ..Default::default()
+            }).insert(EditorShape).insert(EditorEntity);
+        }
+    }
+    for mut text in text_query.iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            section.value = format!(
+                "Slot {} of {} (1-8)   Left: add / drag   Wheel: resize   [ ]: density   Right: base   Del: remove   S: save   Esc: menu\n{}",
+                editor.slot + 1,
+                editor.layout.players,
+                editor.message
+            );
+            section.style.color = PLAYER_COLOURS[editor.slot % PLAYER_COLOURS.len()];
+        }
+    }
+}
+
+// Show the path of a shot fired straight up from the last placed base, using the
+// same integrator as the aiming tracer
+fn editor_gravity_preview(
+    editor : Res<MapEditor>,
+    source_query : Query<(&GravitySource, &Transform)>,
+    mut tracer_query : Query<(&EditorTracer, &mut Transform, &mut Visibility)>
+) {
+    let bodies = gravity_bodies(&source_query);
+    let start = editor.preview.and_then(
+        |(index, base_index)| editor.layout.asteroids.get(index).and_then(
+            |asteroid| asteroid.bases.get(base_index).map(
+                |base| (asteroid.base_position(base.angle), base.angle)
+            )
+        )
+    );
+    for (tracer, mut transform, mut visibility) in tracer_query.iter_mut() {
+        match start {
+            Some((pos, angle)) => {
+                let direction = Vec2::new(-angle.sin(), angle.cos());
+                let position = calculate_position(&bodies, pos + direction * 12.0, tracer.delay, direction * 70.0);
+                transform.translation = Vec3::new(position.x, position.y, 0.2);
+                visibility.is_visible = true;
+            },
+            None => visibility.is_visible = false
+        }
+    }
+}
+
+fn teardown_editor(
+    mut commands : Commands,
+    editor_query : Query<Entity, With<EditorEntity>>
+) {
+    for entity in editor_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// Plugins
+pub struct MapEditorPlugin;
+
+impl Plugin for MapEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapEditor>()
+           .init_resource::<EditorTextures>()
+           .add_system_set(
+             SystemSet::on_enter(AppState::MapEditor)
+               .with_system(setup_editor.system())
+           )
+           .add_system_set(
+             SystemSet::on_update(AppState::MapEditor)
+               .with_system(editor_mouse.system())
+               .with_system(editor_keys.system())
+               .with_system(editor_redraw.system())
+               .with_system(editor_gravity_preview.system())
+           )
+           .add_system_set(
+             SystemSet::on_exit(AppState::MapEditor)
+               .with_system(teardown_editor.system())
+           );
+    }
+}
\ No newline at end of file
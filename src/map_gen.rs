@@ -0,0 +1,252 @@
+use bevy::prelude::*;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::map::{MapLayout, AsteroidLayout, BaseLayout, default_asteroid_texture, default_background};
+use super::app_state::AppState;
+
+// Small seeded random generator (splitmix64) so a seed always gives the same map
+pub struct MapRng {
+    state : u64
+}
+
+impl MapRng {
+    pub fn new(seed : u64) -> MapRng {
+        MapRng { state : seed }
+    }
+
+    pub fn next_u64(self : &mut Self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f32(self : &mut Self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(self : &mut Self, min : f32, max : f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+// Resources
+pub struct MapSettings {
+    // Seed to use for the next map, a fresh one is picked each game if not set
+    pub seed : Option<u64>,
+    pub asteroid_count : usize,
+    pub min_radius : f32,
+    pub max_radius : f32,
+    pub min_density : f32,
+    pub max_density : f32,
+    // Half the width and height of the area asteroids are placed in
+    pub arena : Vec2,
+    // Smallest gap left between asteroid surfaces
+    pub asteroid_gap : f32,
+    pub bases_per_player : usize,
+    pub min_base_separation : f32
+}
+
+impl Default for MapSettings {
+    fn default() -> Self {
+        MapSettings {
+            seed : None,
+            asteroid_count : 5,
+            min_radius : 30.0,
+            max_radius : 70.0,
+            min_density : 0.7,
+            max_density : 1.4,
+            arena : Vec2::new(560.0, 300.0),
+            asteroid_gap : 40.0,
+            bases_per_player : 2,
+            min_base_separation : 90.0
+        }
+    }
+}
+
+// The seed used to build the current map
+#[derive(Default)]
+pub struct MapSeed {
+    pub seed : u64,
+    // False when the map was loaded from a file, so there is no seed to show
+    pub generated : bool
+}
+
+#[derive(Component)]
+struct MapSeedUI;
+
+pub fn choose_seed(settings : &MapSettings) -> u64 {
+    settings.seed.unwrap_or_else(time_seed)
+}
+
+// A different seed every run, for when none has been asked for
+pub fn time_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(
+        |duration| duration.as_nanos() as u64
+    ).unwrap_or(0)
+}
+
+// A base for each player, or None if they do not all fit
+fn place_round(
+    rng : &mut MapRng,
+    asteroids : &Vec<AsteroidLayout>,
+    placed : &Vec<Vec2>,
+    player_count : usize,
+    separation : f32,
+    attempts : usize
+) -> Option<Vec<(usize, BaseLayout, Vec2)>> {
+    let mut round : Vec<(usize, BaseLayout, Vec2)> = Vec::new();
+    for player in 0..player_count {
+        let found = (0..attempts).find_map(|_| {
+            let index = (rng.next_u64() % asteroids.len() as u64) as usize;
+            let angle = rng.range(-std::f32::consts::PI, std::f32::consts::PI);
+            let pos = asteroids[index].base_position(angle);
+            let crowded = placed.iter().chain(round.iter().map(|(_, _, other)| other)).any(
+                |other| other.distance(pos) < separation
+            );
+            let buried = asteroids.iter().enumerate().any(
+                |(other_index, other)| other_index != index && other.pos().distance(pos) < other.radius + 25.0
+            );
+            if crowded || buried {
+                None
+            } else {
+                Some((index, BaseLayout { angle : angle, player : player }, pos))
+            }
+        })?;
+        round.push(found);
+    }
+    Some(round)
+}
+
+pub fn generate_map(settings : &MapSettings, seed : u64, player_count : usize) -> MapLayout {
+    let mut rng = MapRng::new(seed);
+    let attempts = 500;
+    // More players need more rock to put their bases on
+    let asteroid_count = settings.asteroid_count.max(player_count + 1);
+    // Asteroids, rejecting any which overlap or leave the arena
+    let mut asteroids : Vec<AsteroidLayout> = Vec::new();
+    for _ in 0..attempts {
+        if asteroids.len() >= asteroid_count {
+            break;
+        }
+        let radius = rng.range(settings.min_radius, settings.max_radius);
+        let pos = Vec2::new(
+            rng.range(-settings.arena.x + radius, settings.arena.x - radius),
+            rng.range(-settings.arena.y + radius, settings.arena.y - radius)
+        );
+        let overlaps = asteroids.iter().any(
+            |other| other.pos().distance(pos) < other.radius + radius + settings.asteroid_gap
+        );
+        if !overlaps {
+            asteroids.push(AsteroidLayout { 
+                x : pos.x, 
+                y : pos.y, 
+                radius : radius, 
+                density : rng.range(settings.min_density, settings.max_density),
+                texture : default_asteroid_texture(),
+                bases : Vec::new() 
+            });
+        }
+    }
+    let mut layout = MapLayout {
+        background : default_background(),
+        players : player_count,
+        asteroids : asteroids
+    };
+    if layout.asteroids.is_empty() {
+        return layout;
+    }
+    let asteroids = &mut layout.asteroids;
+    // Bases, placed a round at a time with one for each player so everyone gets the same
+    // number. A round which does not fit is left out, except the first which is squeezed
+    // closer together until it does so every player has a base
+    let mut placed : Vec<Vec2> = Vec::new();
+    let mut separation = settings.min_base_separation;
+    let mut rounds = 0;
+    while rounds < settings.bases_per_player {
+        match place_round(&mut rng, asteroids, &placed, player_count, separation, attempts) {
+            Some(round) => {
+                for (index, base, pos) in round {
+                    asteroids[index].bases.push(base);
+                    placed.push(pos);
+                }
+                rounds += 1;
+            },
+            None if rounds == 0 && separation > 1.0 => separation /= 2.0,
+            None => break
+        }
+    }
+    layout
+}
+
+fn setup_seed_ui(commands : &mut Commands, seed : u64, font : Handle<Font>) {
+    commands.spawn_bundle(TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(20.0),
+                bottom: Val::Px(20.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text::with_section(
+            format!("Seed {}", seed),
+            TextStyle {
+                font: font,
+                font_size: 20.0,
+                color: Color::rgb(0.7, 0.7, 0.7),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    }).insert(MapSeedUI);
+}
+
+// Show the seed of a generated map so it can be played again
+fn seed_ui_changed(
+    mut commands : Commands,
+    map_seed : Res<MapSeed>,
+    asset_server : Res<AssetServer>
+) {
+    if map_seed.is_changed() && map_seed.generated {
+        setup_seed_ui(&mut commands, map_seed.seed, asset_server.load("fonts/FiraSans-Bold.ttf"));
+    }
+}
+
+fn teardown_seed_ui(
+    mut commands : Commands,
+    ui_query : Query<Entity, With<MapSeedUI>>
+) {
+    for ui in ui_query.iter() {
+        commands.entity(ui).despawn_recursive();
+    }
+}
+
+// Plugins
+pub struct MapGenPlugin;
+
+impl Plugin for MapGenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MapSettings>()
+           .init_resource::<MapSeed>();
+    }
+}
+
+pub struct MapSeedUiPlugin;
+
+impl Plugin for MapSeedUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+             SystemSet::on_update(AppState::InGame)
+               .with_system(seed_ui_changed.system())
+           )
+           .add_system_set(
+             SystemSet::on_exit(AppState::InGame)
+               .with_system(teardown_seed_ui.system())
+           );
+    }
+}
\ No newline at end of file
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+
+use super::weapon::{Weapon, ActiveWeapons, Impact};
+use super::base::Base;
+use super::asteroids::Asteroid;
+use super::turn::{TurnStart, TurnEnd};
+use super::explosion::Explode;
+use super::app_state::AppState;
+use super::physics::{PhysicsStage, PhysicsLabel};
+
+// Components
+
+// A mine stuck to the surface of an asteroid, it is positioned like a base
+// so it follows the surface as the asteroid changes
+#[derive(Component)]
+pub struct Mine {
+    pub angle : f32,
+    pub offset : f32,
+    pub armed : bool,
+    pub trigger_radius : f32,
+    // The player who laid it
+    pub owner : Option<Entity>
+}
+
+// Events
+pub struct MineLand {
+    pub mine : Entity,
+    pub asteroid : Entity
+}
+
+// Resources
+pub struct MineTextures {
+    active : Handle<Image>,
+    inactive : Handle<Image>
+}
+
+impl FromWorld for MineTextures {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        MineTextures {
+            active : asset_server.load("images/mines_active.png"),
+            inactive : asset_server.load("images/mines_inactive.png")
+        }
+    }
+}
+
+// Systems
+
+// Stick a mine weapon to the asteroid it hit, it stops being a weapon in flight
+// so the turn ends if it was the last one
+fn mine_land(
+    mut commands : Commands,
+    mut events : EventReader<MineLand>,
+    transform_query : Query<&GlobalTransform>,
+    asteroid_query : Query<&Asteroid>,
+    weapon_query : Query<&Weapon>,
+    mut active_weapons : ResMut<ActiveWeapons>,
+    mut events_turn : EventWriter<TurnEnd>
+) {
+    for event in events.iter() {
+        if !active_weapons.retire(event.mine, &mut events_turn) {
+            continue;
+        }
+        if let (Ok(mine_transform), Ok(asteroid_transform), Ok(asteroid)) = (
+            transform_query.get(event.mine),
+            transform_query.get(event.asteroid),
+            asteroid_query.get(event.asteroid)
+        ) {
+            let delta = mine_transform.translation - asteroid_transform.translation;
+            let angle = (-delta.x).atan2(delta.y);
+            let offset = -6.0;
+            let radius = asteroid.height(angle) - offset;
+            let owner = weapon_query.get(event.mine).ok().and_then(|weapon| weapon.owner);
+            commands.entity(event.mine).remove::<Weapon>().remove::<Impact>().insert(Mine {
+                angle : angle,
+                offset : offset,
+                armed : false,
+                trigger_radius : 40.0,
+                owner : owner
+            }).insert(Transform {
+                translation : Vec3::new(-radius * angle.sin(), radius * angle.cos(), 0.0),
+                rotation : Quat::from_rotation_z(angle),
+                scale : Vec3::new(1.0, 1.0, 1.0)
+            }).insert(Parent(event.asteroid));
+        } else {
+            commands.entity(event.mine).despawn_recursive();
+        }
+    }
+}
+
+// Mines that have landed become live at the start of the next turn
+fn mine_arm(
+    mut events : EventReader<TurnStart>,
+    mut mine_query : Query<&mut Mine>
+) {
+    for _ in events.iter() {
+        for mut mine in mine_query.iter_mut() {
+            if !mine.armed {
+                mine.armed = true;
+            }
+        }
+    }
+}
+
+fn mine_texture_changed(
+    mut mine_query : Query<(&Mine, &mut Handle<Image>), Changed<Mine>>,
+    textures : Res<MineTextures>
+) {
+    for (mine, mut texture) in mine_query.iter_mut() {
+        *texture = if mine.armed {
+            textures.active.clone()
+        } else {
+            textures.inactive.clone()
+        };
+    }
+}
+
+// Mines and bases sit on asteroids, so their place in the world comes from the
+// asteroid as it is after this step rather than the transforms from the last frame
+fn mine_trigger(
+    mut commands : Commands,
+    mine_query : Query<(Entity, &Mine, &Transform, &Parent)>,
+    weapon_query : Query<&Transform, With<Weapon>>,
+    base_query : Query<(&Transform, &Parent), With<Base>>,
+    asteroid_query : Query<&Transform, With<Asteroid>>,
+    mut events_explosion : EventWriter<Explode>
+) {
+    let on_asteroid = |transform : &Transform, parent : &Parent| asteroid_query.get(parent.0).ok().map(
+        |asteroid_transform| asteroid_transform.mul_vec3(transform.translation)
+    );
+    let targets : Vec<Vec3> = weapon_query.iter().map(
+        |transform| transform.translation
+    ).chain(base_query.iter().filter_map(
+        |(transform, parent)| on_asteroid(transform, parent)
+    )).collect();
+    for (entity, mine, mine_transform, parent) in mine_query.iter() {
+        if !mine.armed {
+            continue;
+        }
+        let pos = match on_asteroid(mine_transform, parent) {
+            Some(pos) => pos,
+            None => continue
+        };
+        let triggered = targets.iter().any(
+            |target| target.distance(pos) < mine.trigger_radius
+        );
+        if triggered {
+            events_explosion.send(Explode { 
+                pos : Vec2::new(pos.x, pos.y), 
+                power : 40.0, 
+                asteroid_multiplier : 1.0, 
+                source : mine.owner 
+            });
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+// Plugins
+pub struct MinePlugin;
+
+impl Plugin for MinePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MineLand>()
+           .add_system_set(
+             SystemSet::on_update(AppState::InGame)
+               .with_system(mine_land.system())
+               .with_system(mine_arm.system())
+           )
+           .add_system_set_to_stage(
+             PhysicsStage,
+             SystemSet::new()
+               .with_system(mine_trigger.system().after(PhysicsLabel::Move).after(PhysicsLabel::Separate))
+           );
+    }
+}
+
+pub struct MineRenderPlugin;
+
+impl Plugin for MineRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MineTextures>()
+           .add_system_set(
+             SystemSet::on_update(AppState::InGame)
+               .with_system(mine_texture_changed.system())
+           );
+    }
+}
\ No newline at end of file
@@ -0,0 +1,117 @@
+use bevy::prelude::*;
+
+use super::asteroids::{Asteroid, AsteroidMotion};
+use super::gravity::{GravitySource, gravity_bodies, body_gravity};
+use super::physics::{PhysicsStage, PhysicsLabel, PHYSICS_STEP};
+use super::app_state::AppState;
+
+// Orbital mode, asteroids keep their speed and pull on each other in the same way
+// they pull on weapons. Bases and mines ride along as children
+
+// Resources
+pub struct OrbitSettings {
+    pub enabled : bool
+}
+
+impl Default for OrbitSettings {
+    fn default() -> Self {
+        OrbitSettings { enabled : false }
+    }
+}
+
+// Whether the asteroids of this game have been set moving yet
+#[derive(Default)]
+struct OrbitState {
+    started : bool
+}
+
+// Systems
+
+// Start each asteroid on a circular path around the centre of mass of the map, using
+// the pull it feels from everything else
+fn orbit_start(
+    settings : Res<OrbitSettings>,
+    mut state : ResMut<OrbitState>,
+    mut asteroid_query : Query<(&Asteroid, &mut AsteroidMotion, &Transform)>,
+    source_query : Query<(&GravitySource, &Transform)>
+) {
+    if !settings.enabled || state.started || asteroid_query.is_empty() {
+        return;
+    }
+    state.started = true;
+    let mut total_mass = 0.0;
+    let mut weighted = Vec2::ZERO;
+    for (asteroid, _, transform) in asteroid_query.iter() {
+        let mass = asteroid.mass();
+        total_mass += mass;
+        weighted += Vec2::new(transform.translation.x, transform.translation.y) * mass;
+    }
+    let centre = weighted / total_mass;
+    let bodies = gravity_bodies(&source_query);
+    // Do in 2 stages so the map as a whole can be kept from drifting away
+    let mut momentum = Vec2::ZERO;
+    let velocities : Vec<Vec2> = asteroid_query.iter().map(
+        |(asteroid, _, transform)| {
+            let pos = Vec2::new(transform.translation.x, transform.translation.y);
+            let outward = pos - centre;
+            if outward.length() < 1.0 {
+                return Vec2::ZERO;
+            }
+            // Speed where the pull towards the centre just keeps it turning
+            let pull = body_gravity(&bodies, pos, 1.0);
+            let inward = (-pull.dot(outward.normalize())).max(0.0);
+            let speed = (inward * outward.length()).sqrt();
+            let velocity = outward.perp().normalize() * speed;
+            momentum += velocity * asteroid.mass();
+            velocity
+        }
+    ).collect();
+    let drift = momentum / total_mass;
+    for ((_, mut motion, _), velocity) in asteroid_query.iter_mut().zip(velocities) {
+        motion.velocity += velocity - drift;
+    }
+}
+
+fn orbit_gravity(
+    settings : Res<OrbitSettings>,
+    mut asteroid_query : Query<(&mut AsteroidMotion, &Transform), With<Asteroid>>,
+    source_query : Query<(&GravitySource, &Transform)>
+) {
+    if !settings.enabled {
+        return;
+    }
+    let bodies = gravity_bodies(&source_query);
+    for (mut motion, transform) in asteroid_query.iter_mut() {
+        let pos = Vec2::new(transform.translation.x, transform.translation.y);
+        motion.velocity += body_gravity(&bodies, pos, PHYSICS_STEP);
+    }
+}
+
+fn teardown_orbits(
+    mut state : ResMut<OrbitState>
+) {
+    state.started = false;
+}
+
+// Plugins
+pub struct OrbitPlugin;
+
+impl Plugin for OrbitPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OrbitSettings>()
+           .init_resource::<OrbitState>()
+           .add_system_set(
+             SystemSet::on_update(AppState::InGame)
+               .with_system(orbit_start.system())
+           )
+           .add_system_set_to_stage(
+             PhysicsStage,
+             SystemSet::new()
+               .with_system(orbit_gravity.system().label(PhysicsLabel::Orbit).after(PhysicsLabel::Move))
+           )
+           .add_system_set(
+             SystemSet::on_exit(AppState::InGame)
+               .with_system(teardown_orbits.system())
+           );
+    }
+}
@@ -0,0 +1,192 @@
+use bevy::prelude::*;
+
+use super::app_state::AppState;
+use super::turn::TurnState;
+
+// Based on bevy example menu code
+
+struct ButtonMaterials {
+    normal: Color,
+    hovered: Color,
+    pressed: Color,
+}
+
+impl FromWorld for ButtonMaterials {
+    fn from_world(_: &mut World) -> Self {
+        ButtonMaterials {
+            normal: Color::rgb(0.15, 0.15, 0.15),
+            hovered: Color::rgb(0.25, 0.25, 0.25),
+            pressed: Color::rgb(0.35, 0.75, 0.35),
+        }
+    }
+}
+
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component)]
+enum PauseButton {
+    Resume,
+    Restart,
+    Quit
+}
+
+fn spawn_pause_menu(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    button_materials: &ButtonMaterials
+) {
+    let text_style = TextStyle {
+        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        font_size: 40.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    // Dim the game behind the buttons
+    commands.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            position_type: PositionType::Absolute,
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+        ..Default::default()
+    })
+    .insert(PauseMenu)
+    .with_children(|parent| {
+        parent.spawn_bundle(TextBundle {
+            text: Text::with_section(
+                "Paused",
+                TextStyle { font_size: 60.0, ..text_style.clone() },
+                Default::default(),
+            ),
+            style: Style {
+                margin: Rect::all(Val::Px(20.0)),
+                ..Default::default()
+            },
+            ..Default::default()
+        });
+        for (button, label) in [
+            (PauseButton::Resume, "Resume"),
+            (PauseButton::Restart, "Restart match"),
+            (PauseButton::Quit, "Quit to menu")
+        ] {
+            parent.spawn_bundle(ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+                    margin: Rect::all(Val::Px(10.0)),
+                    // horizontally center child text
+                    justify_content: JustifyContent::Center,
+                    // vertically center child text
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                color: button_materials.normal.into(),
+                ..Default::default()
+            })
+            .insert(button)
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        label,
+                        text_style.clone(),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            });
+        }
+    });
+}
+
+fn despawn_pause_menu(
+    commands: &mut Commands,
+    menu_query: &Query<Entity, With<PauseMenu>>
+) {
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+// Escape pauses the game or resumes it if it is already paused
+fn pause_toggle(
+    mut commands: Commands,
+    key_input: Res<Input<KeyCode>>,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<ButtonMaterials>,
+    mut turn_state: ResMut<TurnState>,
+    menu_query: Query<Entity, With<PauseMenu>>
+) {
+    if !key_input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    if turn_state.paused() {
+        turn_state.resume();
+        despawn_pause_menu(&mut commands, &menu_query);
+    } else {
+        turn_state.pause();
+        spawn_pause_menu(&mut commands, &asset_server, &button_materials);
+    }
+}
+
+fn pause_menu(
+    mut commands: Commands,
+    mut state: ResMut<State<AppState>>,
+    mut turn_state: ResMut<TurnState>,
+    button_materials: Res<ButtonMaterials>,
+    menu_query: Query<Entity, With<PauseMenu>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor, &PauseButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = button_materials.pressed.into();
+                match button {
+                    PauseButton::Resume => {
+                        turn_state.resume();
+                        despawn_pause_menu(&mut commands, &menu_query);
+                    },
+                    // Leaving the game tears it down, the menu goes with it
+                    PauseButton::Restart => state.set(AppState::Restarting).unwrap(),
+                    PauseButton::Quit => state.set(AppState::MainMenu).unwrap()
+                }
+                break;
+            }
+            Interaction::Hovered => {
+                *color = button_materials.hovered.into();
+            }
+            Interaction::None => {
+                *color = button_materials.normal.into();
+            }
+        }
+    }
+}
+
+fn teardown_pause_menu(
+    mut commands: Commands,
+    menu_query: Query<Entity, With<PauseMenu>>
+) {
+    despawn_pause_menu(&mut commands, &menu_query);
+}
+
+pub struct PauseMenuPlugin;
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ButtonMaterials>()
+           .add_system_set(
+               SystemSet::on_update(AppState::InGame)
+                 .with_system(pause_toggle.system())
+                 .with_system(pause_menu.system())
+                )
+           .add_system_set(
+               SystemSet::on_exit(AppState::InGame)
+                 .with_system(teardown_pause_menu.system())
+               );
+    }
+}
@@ -0,0 +1,140 @@
+use bevy::prelude::*;
+use bevy::ecs::schedule::ShouldRun;
+
+use super::app_state::AppState;
+use super::turn::TurnState;
+
+// Weapons and moving asteroids are stepped with a fixed time step in their own stage so
+// the same shot always follows the same path, however fast the game is running.
+// The aiming tracer predicts with the same step
+
+pub const PHYSICS_STEP : f32 = 0.02;
+
+// Most time a single frame can catch up on, so a long stall doesn't freeze the game
+// running all the steps it missed
+const MAX_CATCH_UP : f32 = 0.25;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct PhysicsStage;
+
+// Ordering of the systems within a step
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum PhysicsLabel {
+    // Change the thrust of weapons
+    Steer,
+    // Move weapons
+    Move,
+    // Pull asteroids together when orbiting
+    Orbit,
+    // Move asteroids
+    Drift,
+    // Separate asteroids which have run into each other
+    Separate
+}
+
+// Resources
+
+// Time waiting to be stepped through
+pub struct PhysicsClock {
+    accumulator : f32,
+    looping : bool,
+    // Only step on time passed to advance instead of the frame time, for replays and tests
+    pub manual : bool,
+    // Steps taken since the game started
+    pub steps : u64
+}
+
+impl Default for PhysicsClock {
+    fn default() -> Self {
+        PhysicsClock {
+            accumulator : 0.0,
+            looping : false,
+            manual : false,
+            steps : 0
+        }
+    }
+}
+
+impl PhysicsClock {
+    pub fn advance(self : &mut Self, seconds : f32) {
+        self.accumulator += seconds;
+    }
+
+    // Take a step if there is enough time waiting
+    fn take_step(self : &mut Self) -> bool {
+        if self.accumulator >= PHYSICS_STEP {
+            self.accumulator -= PHYSICS_STEP;
+            self.steps += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn reset(self : &mut Self) {
+        self.accumulator = 0.0;
+        self.looping = false;
+        self.steps = 0;
+    }
+}
+
+// Semi-implicit Euler step of a weapon in flight, gravity is the change in velocity over
+// the step. Returns the new position and thrust
+pub fn flight_step(pos : Vec2, thrust : Vec2, gravity : Vec2) -> (Vec2, Vec2) {
+    let thrust = thrust + gravity;
+    (pos + thrust * PHYSICS_STEP, thrust)
+}
+
+// Run the physics stage once for each step of time waiting, the frame time is only added
+// on the first check each frame. Nothing moves while the game is paused
+fn physics_should_run(
+    mut clock : ResMut<PhysicsClock>,
+    time : Res<Time>,
+    state : Res<State<AppState>>,
+    turn_state : Res<TurnState>
+) -> ShouldRun {
+    if state.current() != &AppState::InGame || turn_state.paused() {
+        // Time passed while paused is dropped rather than caught up on
+        if turn_state.paused() {
+            clock.accumulator = 0.0;
+        }
+        clock.looping = false;
+        return ShouldRun::No;
+    }
+    if !clock.looping && !clock.manual {
+        clock.accumulator = (clock.accumulator + time.delta_seconds()).min(MAX_CATCH_UP);
+    }
+    if clock.take_step() {
+        clock.looping = true;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        clock.looping = false;
+        ShouldRun::No
+    }
+}
+
+fn teardown_physics(
+    mut clock : ResMut<PhysicsClock>
+) {
+    clock.reset();
+}
+
+// Plugins
+
+// Needs adding before the plugins which put systems in the physics stage
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhysicsClock>()
+           .add_stage_after(
+             CoreStage::Update,
+             PhysicsStage,
+             SystemStage::parallel().with_run_criteria(physics_should_run)
+           )
+           .add_system_set(
+             SystemSet::on_exit(AppState::InGame)
+               .with_system(teardown_physics.system())
+           );
+    }
+}
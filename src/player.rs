@@ -5,7 +5,24 @@ use std::collections::BTreeSet;
 use super::base::{BaseOwner,BaseDestroyed};
 use super::turn::{TurnStart, TurnEnd};
 use super::app_state::AppState;
-use super::victory_menu::Victory;
+use super::inventory::Inventory;
+use super::ai::Controller;
+
+pub const MIN_PLAYERS : usize = 2;
+pub const MAX_PLAYERS : usize = 8;
+pub const MAX_TEAMS : usize = 4;
+
+// Colours players and the player slots of maps are shown in
+pub const PLAYER_COLOURS : [Color; MAX_PLAYERS] = [
+    Color::rgb(0.75, 0.15, 0.15),
+    Color::rgb(0.15, 0.75, 0.15),
+    Color::rgb(0.2, 0.35, 0.9),
+    Color::rgb(0.85, 0.75, 0.15),
+    Color::rgb(0.7, 0.2, 0.8),
+    Color::rgb(0.15, 0.75, 0.8),
+    Color::rgb(0.9, 0.5, 0.1),
+    Color::rgb(0.9, 0.9, 0.9)
+];
 
 #[derive(Default)]
 pub struct PlayerOrder {
@@ -13,41 +30,174 @@ pub struct PlayerOrder {
     pub current : usize
 }
 
+// Winning team of the last game and everyone on it, whether or not they still had
+// bases at the end. No team if everyone lost
+#[derive(Default)]
+pub struct Victory {
+    pub team : Option<usize>,
+    pub members : Vec<Player>
+}
+
+// Who is playing in the next game, set up in the lobby
+#[derive(Clone)]
+pub struct PlayerConfig {
+    pub name : String,
+    pub colour : Color,
+    // Only used when playing in teams
+    pub team : usize,
+    pub controller : Controller
+}
+
+pub struct PlayerSettings {
+    pub players : Vec<PlayerConfig>,
+    // Play in teams which win together, otherwise it is everyone for themselves
+    pub teams : bool,
+    // Whether players can damage the bases of their team mates
+    pub friendly_fire : bool
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        PlayerSettings {
+            players : vec![
+                PlayerConfig { 
+                    name : "Robert".to_string(), 
+                    colour : PLAYER_COLOURS[0], 
+                    team : 0, 
+                    controller : Controller::Human 
+                },
+                PlayerConfig { 
+                    name : "James".to_string(), 
+                    colour : PLAYER_COLOURS[1], 
+                    team : 1, 
+                    controller : Controller::Human 
+                }
+            ],
+            teams : false,
+            friendly_fire : false
+        }
+    }
+}
+
+impl PlayerSettings {
+    fn colour_used(self : &Self, colour : Color) -> bool {
+        self.players.iter().any(|player| player.colour == colour)
+    }
+
+    // Add a player with the first colour nobody has, returns false if the game is full
+    pub fn add(self : &mut Self) -> bool {
+        if self.players.len() >= MAX_PLAYERS {
+            return false;
+        }
+        let colour = PLAYER_COLOURS.iter().cloned().find(
+            |colour| !self.colour_used(*colour)
+        ).unwrap_or(PLAYER_COLOURS[0]);
+        let name = format!("Player {}", self.players.len() + 1);
+        // Alternate between the first two teams to start with
+        let team = self.players.len() % 2;
+        self.players.push(PlayerConfig { 
+            name : name, 
+            colour : colour, 
+            team : team, 
+            controller : Controller::Human 
+        });
+        true
+    }
+
+    // Returns false if that would leave too few players for a game
+    pub fn remove(self : &mut Self, index : usize) -> bool {
+        if self.players.len() <= MIN_PLAYERS || index >= self.players.len() {
+            return false;
+        }
+        self.players.remove(index);
+        true
+    }
+
+    pub fn next_team(self : &mut Self, index : usize) {
+        if let Some(player) = self.players.get_mut(index) {
+            player.team = (player.team + 1) % MAX_TEAMS;
+        }
+    }
+
+    // Team a player is on in the game, everyone is on their own team unless teams are
+    // turned on and there are at least two of them
+    pub fn team_of(self : &Self, index : usize) -> usize {
+        let first_team = self.players.first().map(|player| player.team);
+        let several_teams = self.players.iter().any(|player| Some(player.team) != first_team);
+        if self.teams && several_teams {
+            self.players[index].team
+        } else {
+            index
+        }
+    }
+
+    // Switch a player between a person and the computer at each difficulty
+    pub fn next_controller(self : &mut Self, index : usize) {
+        if let Some(player) = self.players.get_mut(index) {
+            player.controller = player.controller.next();
+        }
+    }
+
+    // Move a player on to the next colour nobody else has
+    pub fn next_colour(self : &mut Self, index : usize) {
+        let current = match self.players.get(index) {
+            Some(player) => player.colour,
+            None => return
+        };
+        let start = PLAYER_COLOURS.iter().position(|colour| *colour == current).unwrap_or(0);
+        for offset in 1..PLAYER_COLOURS.len() {
+            let colour = PLAYER_COLOURS[(start + offset) % PLAYER_COLOURS.len()];
+            if !self.colour_used(colour) {
+                self.players[index].colour = colour;
+                return;
+            }
+        }
+    }
+}
+
 #[derive(Clone, Component)]
 pub struct Player {
     pub name : String,
     bases : BTreeSet<Entity>,
     current_base : usize,
-    pub colour : Color
+    pub colour : Color,
+    pub team : usize,
+    pub controller : Controller
 }
 
+// Different players on the same team
+pub fn team_mates(player_query : &Query<&Player>, a : Entity, b : Entity) -> bool {
+    if a == b {
+        return false;
+    }
+    match (player_query.get(a), player_query.get(b)) {
+        (Ok(player_a), Ok(player_b)) => player_a.team == player_b.team,
+        _ => false
+    }
+}
+
+// Spawn the players for a game in turn order
 pub fn setup_players(
     commands : &mut Commands,
     player_order : &mut PlayerOrder,
-    font : Handle<Font>
+    settings : &PlayerSettings
 ) -> Vec<Player> {
-    let players : Vec<Player> = [
-        Player {
-            name : "Robert".to_string(), 
-            bases : BTreeSet::new(), 
-            current_base: 0,
-            colour : Color::rgb(0.75, 0.15, 0.15).into(),
-        },
-        Player {
-            name : "James".to_string(), 
-            bases : BTreeSet::new(), 
-            current_base: 0,
-            colour : Color::rgb(0.15, 0.75, 0.15).into(),
+    let players : Vec<Player> = settings.players.iter().enumerate().map(
+        |(index, config)| Player {
+            name : config.name.clone(),
+            bases : BTreeSet::new(),
+            current_base : 0,
+            colour : config.colour,
+            team : settings.team_of(index),
+            controller : config.controller
         }
-    ].to_vec();
-    let player_1 = commands.spawn().insert(players[0].clone() ).id();
-    let player_2 = commands.spawn().insert(players[1].clone() ).id();
-    player_order.order.push(player_1);
-    player_order.order.push(player_2);
+    ).collect();
+    for player in players.iter() {
+        let entity = commands.spawn().insert(player.clone()).insert(Inventory::default()).id();
+        player_order.order.push(entity);
+    }
     player_order.current = 0;
 
-    setup_player_ui(commands, &players, font);
-
     players
 }
 
@@ -95,6 +245,23 @@ fn setup_player_ui (
     });
 }
 
+// Show the players once they have been set up
+fn player_ui_added(
+    mut commands : Commands,
+    added_query : Query<Entity, Added<Player>>,
+    player_query : Query<&Player>,
+    player_order : Res<PlayerOrder>,
+    asset_server : Res<AssetServer>
+) {
+    if added_query.iter().next().is_none() {
+        return;
+    }
+    let players : Vec<Player> = player_order.order.iter().filter_map(
+        |player| player_query.get(*player).ok().cloned()
+    ).collect();
+    setup_player_ui(&mut commands, &players, asset_server.load("fonts/FiraSans-Bold.ttf"));
+}
+
 fn player_ui_current(
     mut player_ui_query : Query<(&PlayerUI, &mut Text)>,
     player_order : Res<PlayerOrder>,
@@ -124,23 +291,36 @@ fn base_owned(
 fn base_lost(
     mut events : EventReader<BaseDestroyed>,
     mut player_query : Query<&mut Player>,
+    player_order : Res<PlayerOrder>,
     mut victory : ResMut<Victory>,
     mut state: ResMut<State<AppState>>
 ) {
+    let mut lost = false;
     for event in events.iter() {
         for mut player in player_query.iter_mut() {
             player.bases.remove(&event.base);
         }
-        let active_count = player_query.iter_mut().filter(
-            |player| !player.bases.is_empty()
-        ).count();
-        if active_count <= 1 {
-            victory.player = player_query.iter_mut().filter(
-                |player| !player.bases.is_empty()
-            ).map(|p| p.clone()
-            ).next();
-            state.set(AppState::VictoryMenu).unwrap();
-        }
+        lost = true;
+    }
+    // Only decide once all the bases lost this frame are gone, so a shot taking out
+    // the last bases of both sides is a draw
+    if !lost {
+        return;
+    }
+    let active_teams : BTreeSet<usize> = player_query.iter().filter(
+        |player| !player.bases.is_empty()
+    ).map(
+        |player| player.team
+    ).collect();
+    if active_teams.len() <= 1 {
+        let team = active_teams.into_iter().next();
+        victory.team = team;
+        victory.members = player_order.order.iter().filter_map(
+            |player| player_query.get(*player).ok()
+        ).filter(
+            |player| Some(player.team) == team
+        ).cloned().collect();
+        state.set(AppState::VictoryMenu).unwrap();
     }
 }
 
@@ -151,33 +331,49 @@ fn next_turn(
     mut player_query : Query<&mut Player>
 ) {
     for _ in events_end.iter() {
-        player_order.current += 1;
-        if player_order.current >= player_order.order.len() {
-            player_order.current = 0;
-        }
-        let mut player = player_query.get_mut(player_order.order[player_order.current]).unwrap();
-        player.current_base += 1;
-        if player.current_base >= player.bases.len() {
-            player.current_base = 0;
+        // Players with no bases left are skipped
+        for _ in 0..player_order.order.len() {
+            player_order.current += 1;
+            if player_order.current >= player_order.order.len() {
+                player_order.current = 0;
+            }
+            let mut player = match player_query.get_mut(player_order.order[player_order.current]) {
+                Ok(player) => player,
+                Err(_) => continue
+            };
+            if player.bases.is_empty() {
+                continue;
+            }
+            player.current_base += 1;
+            if player.current_base >= player.bases.len() {
+                player.current_base = 0;
+            }
+            if let Some(next_base) = player.bases.iter().nth(player.current_base).copied() {
+                events_start.send(TurnStart{ new_base :  next_base});
+                break;
+            }
         }
-        let next_base = *player.bases.iter().nth(player.current_base).unwrap();
-        events_start.send(TurnStart{ new_base :  next_base});
     }
 }
 
 fn teardown_players(
     mut commands : Commands,
     player_query : Query<Entity, With<Player>>,
-    player_ui_query : Query<Entity, With<PlayerUI>>,
     mut player_order : ResMut<PlayerOrder>
 ) {
     for player in player_query.iter() {
         commands.entity(player).despawn_recursive();
     }
+    player_order.order.clear();
+}
+
+fn teardown_player_ui(
+    mut commands : Commands,
+    player_ui_query : Query<Entity, With<PlayerUI>>
+) {
     for player_ui in player_ui_query.iter() {
         commands.entity(player_ui).despawn_recursive();
     }
-    player_order.order.clear();
 }
 
 pub struct PlayerPlugin;
@@ -185,16 +381,33 @@ pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<PlayerOrder>()
+           .init_resource::<PlayerSettings>()
+           .init_resource::<Victory>()
            .add_system_set(
               SystemSet::on_update(AppState::InGame)
                 .with_system(base_owned.system())
                 .with_system(base_lost.system())
                 .with_system(next_turn.system())
-                .with_system(player_ui_current.system())
               )
            .add_system_set(
               SystemSet::on_exit(AppState::InGame)
                .with_system(teardown_players.system())
            );
     }
+}
+
+pub struct PlayerUiPlugin;
+
+impl Plugin for PlayerUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+              SystemSet::on_update(AppState::InGame)
+                .with_system(player_ui_added.system())
+                .with_system(player_ui_current.system())
+              )
+           .add_system_set(
+              SystemSet::on_exit(AppState::InGame)
+               .with_system(teardown_player_ui.system())
+           );
+    }
 }
\ No newline at end of file
@@ -0,0 +1,101 @@
+use bevy::math::Vec2;
+
+use std::f32::consts::PI;
+
+// Surface of an asteroid as a height above its centre at evenly spaced angles.
+// Angles follow the base convention, zero is straight up and positive turns anticlockwise
+#[derive(Clone)]
+pub struct Terrain {
+    heights : Vec<f32>
+}
+
+pub fn angle_of(point : Vec2) -> f32 {
+    (-point.x).atan2(point.y)
+}
+
+pub fn direction_of(angle : f32) -> Vec2 {
+    Vec2::new(-angle.sin(), angle.cos())
+}
+
+impl Terrain {
+    pub fn new(radius : f32, samples : usize) -> Terrain {
+        Terrain { heights : vec![radius; samples] }
+    }
+
+    pub fn samples(self : &Self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn sample_angle(self : &Self, index : usize) -> f32 {
+        2.0 * PI * (index as f32) / (self.heights.len() as f32)
+    }
+
+    pub fn sample_height(self : &Self, index : usize) -> f32 {
+        self.heights[index % self.heights.len()]
+    }
+
+    // Height of the surface at any angle, interpolating between samples
+    pub fn height(self : &Self, angle : f32) -> f32 {
+        let len = self.heights.len();
+        let position = angle.rem_euclid(2.0 * PI) / (2.0 * PI) * (len as f32);
+        let index = position.floor() as usize;
+        let fraction = position - position.floor();
+        let low = self.heights[index % len];
+        let high = self.heights[(index + 1) % len];
+        low + (high - low) * fraction
+    }
+
+    // Lowest point of the surface under something of a given width standing at an angle
+    pub fn footing(self : &Self, angle : f32, width : f32) -> f32 {
+        let centre = self.height(angle);
+        let half_span = 0.5 * width / centre.max(1.0);
+        let steps = 4;
+        (0..=steps).map(
+            |step| self.height(angle - half_span + 2.0 * half_span * (step as f32) / (steps as f32))
+        ).fold(centre, f32::min)
+    }
+
+    pub fn max_height(self : &Self) -> f32 {
+        self.heights.iter().cloned().fold(0.0, f32::max)
+    }
+
+    pub fn area(self : &Self) -> f32 {
+        // Each sample is a thin wedge of the circle at that height
+        let wedge = PI / (self.heights.len() as f32);
+        self.heights.iter().map(|height| wedge * height * height).sum()
+    }
+
+    // Radius of a circle with the same area
+    pub fn equivalent_radius(self : &Self) -> f32 {
+        (self.area() / PI).sqrt()
+    }
+
+    pub fn contains(self : &Self, point : Vec2) -> bool {
+        point.length() < self.height(angle_of(point))
+    }
+
+    // Remove the rock inside a circle, returns true if anything was removed.
+    // Rock above a buried hole collapses down into it
+    pub fn crater(self : &mut Self, centre : Vec2, radius : f32) -> bool {
+        let mut changed = false;
+        let len = self.heights.len();
+        for index in 0..len {
+            let direction = direction_of(2.0 * PI * (index as f32) / (len as f32));
+            // Where the ray from the centre along this sample enters and leaves the circle
+            let along = direction.dot(centre);
+            let discriminant = along * along - centre.length_squared() + radius * radius;
+            if discriminant <= 0.0 {
+                continue;
+            }
+            let root = discriminant.sqrt();
+            let height = self.heights[index];
+            let start = (along - root).max(0.0);
+            let end = (along + root).min(height);
+            if end > start {
+                self.heights[index] = (height - (end - start)).max(0.0);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
\ No newline at end of file
@@ -2,7 +2,7 @@ use bevy::prelude::*;
 
 use super::app_state::AppState;
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum TurnPhase {
     Aiming, 
     Firing,
@@ -20,7 +20,75 @@ pub struct TurnState {
     pub phase : TurnPhase,
     pub active_base : Option<Entity>,
     pub firing_angle : f32,
-    pub power : f32
+    pub power : f32,
+    // Seconds left to aim, None if there is no limit or it has already run out
+    pub time_left : Option<f32>,
+    // Phase to go back to when the game is resumed
+    resume_phase : TurnPhase
+}
+
+impl TurnState {
+    pub fn paused(self : &Self) -> bool {
+        self.phase == TurnPhase::Paused
+    }
+
+    // Freeze the turn wherever it has got to
+    pub fn pause(self : &mut Self) {
+        if !self.paused() {
+            self.resume_phase = self.phase;
+            self.phase = TurnPhase::Paused;
+        }
+    }
+
+    pub fn resume(self : &mut Self) {
+        if self.paused() {
+            self.phase = self.resume_phase;
+        }
+    }
+}
+
+// What happens when a player runs out of time to aim
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimeoutAction {
+    // Fire the selected weapon at the current angle and power
+    Fire,
+    // Pass the turn to the next player
+    Skip
+}
+
+// Resources
+pub struct TurnTimerSettings {
+    // Seconds each player has to aim, no limit if not set
+    pub limit : Option<f32>,
+    pub on_timeout : TimeoutAction
+}
+
+impl Default for TurnTimerSettings {
+    fn default() -> Self {
+        TurnTimerSettings {
+            limit : None,
+            on_timeout : TimeoutAction::Fire
+        }
+    }
+}
+
+impl TurnTimerSettings {
+    // Choices offered in the menu
+    pub const LIMITS : [Option<f32>; 4] = [None, Some(15.0), Some(30.0), Some(60.0)];
+
+    pub fn next_limit(self : &mut Self) {
+        let index = Self::LIMITS.iter().position(
+            |limit| *limit == self.limit
+        ).map_or(0, |index| index + 1);
+        self.limit = Self::LIMITS[index % Self::LIMITS.len()];
+    }
+
+    pub fn label(self : &Self) -> String {
+        match self.limit {
+            Some(limit) => format!("Turn timer: {}s", limit),
+            None => "Turn timer: Off".to_string()
+        }
+    }
 }
 
 // Event
@@ -30,19 +98,18 @@ pub struct TurnStart {
 
 pub struct TurnEnd;
 
+// The active player has acted, sent once the phase has already moved on to firing
 pub struct TurnFiring;
 
-fn turn_firing_phase_start( 
-    mut turn_state : ResMut<TurnState>,
-    mut events : EventReader<TurnFiring>
-) {
-    for _ in events.iter() {
-        turn_state.phase = TurnPhase::Firing;
-    }
-}
+// The active player took too long to aim
+pub struct TurnTimeout;
+
+#[derive(Component)]
+struct TurnTimerUI;
 
 fn turn_starter(
     mut turn_state : ResMut<TurnState>,
+    settings : Res<TurnTimerSettings>,
     mut events : EventReader<TurnStart>
 )
 {
@@ -51,6 +118,96 @@ fn turn_starter(
         turn_state.active_base = Some(event.new_base);
         turn_state.firing_angle = 0.0;
         turn_state.power = 70.0;
+        turn_state.time_left = settings.limit;
+    }
+}
+
+// Count down the time left to aim, the clock stops once the shot is away
+fn turn_timer(
+    time : Res<Time>,
+    mut turn_state : ResMut<TurnState>,
+    mut events : EventWriter<TurnTimeout>
+) {
+    if turn_state.phase != TurnPhase::Aiming {
+        return;
+    }
+    if let Some(time_left) = turn_state.time_left {
+        let time_left = time_left - time.delta_seconds();
+        if time_left > 0.0 {
+            turn_state.time_left = Some(time_left);
+        } else {
+            turn_state.time_left = None;
+            events.send(TurnTimeout);
+        }
+    }
+}
+
+fn teardown_turn(
+    mut turn_state : ResMut<TurnState>
+) {
+    *turn_state = TurnState::default();
+}
+
+fn setup_turn_timer_ui(
+    mut commands : Commands,
+    asset_server : Res<AssetServer>
+) {
+    // Below the selected weapon icon in the top left corner
+    commands.spawn_bundle(TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(20.0),
+                top: Val::Px(110.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 50.0,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    }).insert(TurnTimerUI);
+}
+
+// Whole seconds left, turning red for the last few
+fn turn_timer_ui_update(
+    turn_state : Res<TurnState>,
+    mut ui_query : Query<&mut Text, With<TurnTimerUI>>
+) {
+    if !turn_state.is_changed() {
+        return;
+    }
+    let warning_time = 5.0;
+    for mut text in ui_query.iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            match turn_state.time_left {
+                Some(time_left) if turn_state.phase == TurnPhase::Aiming => {
+                    section.value = format!("{}", time_left.ceil());
+                    section.style.color = if time_left <= warning_time {
+                        Color::rgb(0.9, 0.2, 0.2)
+                    } else {
+                        Color::rgb(0.9, 0.9, 0.9)
+                    };
+                },
+                _ => section.value = "".to_string()
+            }
+        }
+    }
+}
+
+fn teardown_turn_timer_ui(
+    mut commands : Commands,
+    ui_query : Query<Entity, With<TurnTimerUI>>
+) {
+    for ui in ui_query.iter() {
+        commands.entity(ui).despawn_recursive();
     }
 }
 
@@ -61,11 +218,36 @@ impl Plugin for TurnPlugin {
         app.add_event::<TurnStart>()
            .add_event::<TurnEnd>()
            .add_event::<TurnFiring>()
+           .add_event::<TurnTimeout>()
            .init_resource::<TurnState>()
+           .init_resource::<TurnTimerSettings>()
            .add_system_set(
               SystemSet::on_update(AppState::InGame)
                 .with_system(turn_starter.system())
-                .with_system(turn_firing_phase_start.system())
+                .with_system(turn_timer.system())
+            )
+           .add_system_set(
+              SystemSet::on_exit(AppState::InGame)
+                .with_system(teardown_turn.system())
+            );
+    }
+}
+
+pub struct TurnTimerUiPlugin;
+
+impl Plugin for TurnTimerUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+              SystemSet::on_enter(AppState::InGame)
+                .with_system(setup_turn_timer_ui.system())
+            )
+           .add_system_set(
+              SystemSet::on_update(AppState::InGame)
+                .with_system(turn_timer_ui_update.system())
+            )
+           .add_system_set(
+              SystemSet::on_exit(AppState::InGame)
+                .with_system(teardown_turn_timer_ui.system())
             );
     }
 }
\ No newline at end of file
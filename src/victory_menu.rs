@@ -1,15 +1,10 @@
 use bevy::prelude::*;
 
 use super::app_state::AppState;
-use super::player::Player;
+use super::player::Victory;
 
 // Based on bevy example menu code
 
-
-#[derive(Default)]
-pub struct Victory {
-    pub player : Option<Player>
-}
 struct ButtonMaterials {
     normal: Color,
     hovered: Color,
@@ -37,9 +32,12 @@ fn setup_menu(
     button_materials: Res<ButtonMaterials>,
     victory : Res<Victory>
 ) {
-    let msg = victory.player.as_ref().map(
-        |player| format!("Well done {}!", player.name)
-    ).unwrap_or("No winner everyone lost".to_string());
+    let names : Vec<&str> = victory.members.iter().map(|player| player.name.as_str()).collect();
+    let msg = match (victory.team, names.as_slice()) {
+        (None, _) | (_, []) => "No winner everyone lost".to_string(),
+        (_, [name]) => format!("Well done {}!", name),
+        (Some(team), _) => format!("Well done team {}: {}!", team + 1, names.join(", "))
+    };
     // ui camera
     commands.spawn_bundle(UiCameraBundle::default());
     let button_entity = commands
@@ -134,7 +132,6 @@ pub struct VictoryMenuPlugin;
 impl Plugin for VictoryMenuPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<ButtonMaterials>()
-           .init_resource::<Victory>()
            .add_system_set(
               SystemSet::on_enter(AppState::VictoryMenu)
                 .with_system(setup_menu.system())
@@ -1,9 +1,16 @@
 use bevy::prelude::*;
 
+use std::collections::HashSet;
+
 use super::collide::Box;
 use super::turn::TurnEnd;
 use super::explosion::Explode;
 use super::app_state::AppState;
+use super::base::{Base, BaseOwner};
+use super::player::{Player, team_mates};
+use super::asteroids::{Asteroid, AsteroidImpulse};
+use super::gravity::{GravitySource, SpawnGravityWell, GravityWellType, gravity_bodies, body_gravity};
+use super::physics::{PhysicsStage, PhysicsLabel, PHYSICS_STEP, flight_step};
 
 // Components
 
@@ -11,30 +18,152 @@ use super::app_state::AppState;
 pub struct Weapon {
     pub thrust : Vec2,
     pub fuel : f32,
-    pub size : Vec2
+    pub size : Vec2,
+    pub weapon_type : WeaponType,
+    // The base that launched the weapon
+    pub source : Entity,
+    // The player who fired it
+    pub owner : Option<Entity>
 }
 
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub enum WeaponType {
-    Rocket
+    Rocket,
+    MultiRocket,
+    HomingRocket,
+    Mine,
+    AsteroidMine,
+    AsteroidYank,
+    BlackHole,
+    WhiteHole
+}
+
+impl Default for WeaponType {
+    fn default() -> Self {
+        WeaponType::Rocket
+    }
 }
 
 impl WeaponType {
+    // Every weapon in the order they are cycled through in the inventory
+    pub const ALL : [WeaponType; 8] = [
+        WeaponType::Rocket,
+        WeaponType::MultiRocket,
+        WeaponType::HomingRocket,
+        WeaponType::Mine,
+        WeaponType::AsteroidMine,
+        WeaponType::AsteroidYank,
+        WeaponType::BlackHole,
+        WeaponType::WhiteHole
+    ];
+
     fn size(self : &Self) -> Vec2 {
         match *self {
             WeaponType::Rocket => Vec2::new(12.0, 36.0),
+            WeaponType::MultiRocket => Vec2::new(8.0, 24.0),
+            WeaponType::HomingRocket => Vec2::new(12.0, 36.0),
+            WeaponType::Mine => Vec2::new(16.0, 16.0),
+            WeaponType::AsteroidMine => Vec2::new(10.0, 30.0),
+            WeaponType::AsteroidYank => Vec2::new(10.0, 30.0),
+            WeaponType::BlackHole => Vec2::new(16.0, 16.0),
+            WeaponType::WhiteHole => Vec2::new(16.0, 16.0)
+        }
+    }
+    pub fn fuel(self : &Self) -> f32 {
+        match *self {
+            WeaponType::Rocket => 10.0,
+            WeaponType::MultiRocket => 8.0,
+            WeaponType::HomingRocket => 12.0,
+            WeaponType::Mine => 10.0,
+            WeaponType::AsteroidMine => 10.0,
+            WeaponType::AsteroidYank => 10.0,
+            WeaponType::BlackHole => 2.5,
+            WeaponType::WhiteHole => 2.5
+        }
+    }
+    // Number of projectiles launched together in a single volley
+    fn count(self : &Self) -> usize {
+        match *self {
+            WeaponType::Rocket => 1,
+            WeaponType::MultiRocket => 3,
+            WeaponType::HomingRocket => 1,
+            WeaponType::Mine => 1,
+            WeaponType::AsteroidMine => 1,
+            WeaponType::AsteroidYank => 1,
+            WeaponType::BlackHole => 1,
+            WeaponType::WhiteHole => 1
+        }
+    }
+    // Total angle the volley is fanned across
+    fn spread(self : &Self) -> f32 {
+        match *self {
+            WeaponType::Rocket => 0.0,
+            WeaponType::MultiRocket => 0.35,
+            WeaponType::HomingRocket => 0.0,
+            WeaponType::Mine => 0.0,
+            WeaponType::AsteroidMine => 0.0,
+            WeaponType::AsteroidYank => 0.0,
+            WeaponType::BlackHole => 0.0,
+            WeaponType::WhiteHole => 0.0
+        }
+    }
+    pub fn power(self : &Self) -> f32 {
+        match *self {
+            WeaponType::MultiRocket => 30.0,
+            WeaponType::AsteroidMine => 40.0,
+            WeaponType::AsteroidYank => 5.0,
+            WeaponType::BlackHole => 0.0,
+            WeaponType::WhiteHole => 0.0,
+            _ => 50.0
+        }
+    }
+    // Weapons which go off with their full power where they hit something, rather than
+    // sticking, boring in or pulling on the asteroid
+    pub fn explodes_on_impact(self : &Self) -> bool {
+        match *self {
+            WeaponType::Rocket => true,
+            WeaponType::MultiRocket => true,
+            WeaponType::HomingRocket => true,
+            _ => false
+        }
+    }
+    // Deployables detonate where they are when they run out of fuel
+    fn deployable(self : &Self) -> bool {
+        match *self {
+            WeaponType::BlackHole => true,
+            WeaponType::WhiteHole => true,
+            _ => false
         }
     }
-    fn fuel(self : &Self) -> f32 {
+    fn asteroid_multiplier(self : &Self) -> f32 {
         match *self {
-            WeaponType::Rocket => 10.0
+            WeaponType::AsteroidMine => 3.0,
+            _ => 1.0
         }
     }
 }
 
+// Steers a weapon towards the nearest base not owned by the firing player
+#[derive(Component)]
+pub struct Homing {
+    pub owner : Entity,
+    pub turn_rate : f32
+}
+
+// A weapon tunnelling towards the centre of an asteroid before it detonates
+#[derive(Component)]
+pub struct Boring {
+    pub asteroid : Entity,
+    pub time : f32
+}
+
+// A weapon which has hit something, it stays put until the hit is dealt with
+#[derive(Component)]
+pub struct Impact;
+
 impl Weapon {
-    pub fn bound(self : &Self, transform : &GlobalTransform) -> Box {
+    pub fn bound(self : &Self, transform : &Transform) -> Box {
         let centre = Vec2::new(transform.translation.x, transform.translation.y);
         Box { centre : centre, size : self.size, rotation : transform.rotation }
     }
@@ -54,15 +183,34 @@ pub struct WeaponExplode {
     pub entity : Entity
 }
 
+pub struct WeaponBore {
+    pub entity : Entity,
+    pub asteroid : Entity
+}
+
+pub struct WeaponYank {
+    pub entity : Entity,
+    pub asteroid : Entity
+}
+
 // Resourses 
 pub struct WeaponMaterials {
-    rocket : Handle<Image>
+    rocket : Handle<Image>,
+    rocket_blue : Handle<Image>,
+    mine : Handle<Image>
 }
 
 impl WeaponMaterials {
     pub fn texture(self : &Self, weapon_type : WeaponType) -> Handle<Image> {
         match weapon_type {
-            WeaponType::Rocket => self.rocket.clone()
+            WeaponType::Rocket => self.rocket.clone(),
+            WeaponType::MultiRocket => self.rocket_blue.clone(),
+            WeaponType::HomingRocket => self.rocket.clone(),
+            WeaponType::Mine => self.mine.clone(),
+            WeaponType::AsteroidMine => self.rocket_blue.clone(),
+            WeaponType::AsteroidYank => self.rocket_blue.clone(),
+            WeaponType::BlackHole => self.mine.clone(),
+            WeaponType::WhiteHole => self.mine.clone()
         }
     }
 }
@@ -71,25 +219,58 @@ impl FromWorld for WeaponMaterials {
     fn from_world(world: &mut World) -> Self {
         let asset_server = world.get_resource::<AssetServer>().unwrap();
         let rocket_texture_handle = asset_server.load("images/rocket.png");
+        let rocket_blue_texture_handle = asset_server.load("images/rocket_blue.png");
+        let mine_texture_handle = asset_server.load("images/mines_inactive.png");
         WeaponMaterials {
-            rocket : rocket_texture_handle
+            rocket : rocket_texture_handle,
+            rocket_blue : rocket_blue_texture_handle,
+            mine : mine_texture_handle
+        }
+    }
+}
+
+// Weapons launched this turn which are still in flight, the turn only ends
+// once the last of them is gone
+#[derive(Default)]
+pub struct ActiveWeapons {
+    weapons : HashSet<Entity>
+}
+
+impl ActiveWeapons {
+    pub fn add(self : &mut Self, weapon : Entity) {
+        self.weapons.insert(weapon);
+    }
+
+    // Stop tracking a weapon, returns false if it was already retired this turn.
+    // Sends the turn end when the last weapon in flight is retired
+    pub fn retire(self : &mut Self, weapon : Entity, events_turn : &mut EventWriter<TurnEnd>) -> bool {
+        if !self.weapons.remove(&weapon) {
+            return false;
+        }
+        if self.weapons.is_empty() {
+            events_turn.send(TurnEnd);
         }
+        true
     }
 }
 
 // Systems
 
 pub fn weapon_fuel_update(
-    mut weapon_query: Query<(Entity, &mut Weapon)>, 
+    mut weapon_query: Query<(Entity, &mut Weapon), Without<Impact>>, 
     mut commands: Commands,
-    time: Res<Time>,
-    mut events_turn : EventWriter<TurnEnd>
+    mut active_weapons : ResMut<ActiveWeapons>,
+    mut events_turn : EventWriter<TurnEnd>,
+    mut events_explode : EventWriter<WeaponExplode>
 ) {
     for (entity, mut weapon) in weapon_query.iter_mut() {
-        weapon.fuel -= time.delta_seconds();
+        weapon.fuel -= PHYSICS_STEP;
         if weapon.fuel < 0.0 {
-            events_turn.send(TurnEnd);
-            commands.entity(entity).despawn_recursive();
+            if weapon.weapon_type.deployable() {
+                events_explode.send(WeaponExplode { entity : entity });
+            } else if active_weapons.retire(entity, &mut events_turn) {
+                commands.entity(entity).despawn_recursive();
+            }
         }
     }
 }
@@ -97,28 +278,30 @@ pub fn weapon_fuel_update(
 fn launch_weapon(
     commands: &mut Commands,
     parent_transform : &GlobalTransform,
-    materials : &WeaponMaterials,
-    launch : &Launch
+    launch : &Launch,
+    owner : Option<Entity>,
+    angle : f32
 ) -> Entity {
-    let rocket_rotation = parent_transform.rotation * Quat::from_rotation_z(launch.angle);
+    let rocket_rotation = parent_transform.rotation * Quat::from_rotation_z(angle);
     let direction = rocket_rotation * Vec3::new(0.0, 1.0 ,0.0);
     let offset = direction * launch.offset;
     let thrust = Vec2::new(direction.x, direction.y) * launch.thrust;
     let size = launch.weapon_type.size();
-    commands.spawn_bundle(SpriteBundle {
-        texture: materials.texture(launch.weapon_type),
-        transform: Transform { 
-            translation : parent_transform.translation + offset,
-            rotation : rocket_rotation,
-            scale : Vec3::new(1.0,1.0,1.0)
-        },
-        sprite: Sprite { custom_size : Some(size), ..Default::default()},
-        ..Default::default()
-    }).insert(
+    let transform = Transform { 
+        translation : parent_transform.translation + offset,
+        rotation : rocket_rotation,
+        scale : Vec3::new(1.0,1.0,1.0)
+    };
+    commands.spawn().insert(transform
+    ).insert(GlobalTransform::from(transform)
+    ).insert(
         Weapon{ 
             thrust : thrust, 
             fuel : launch.weapon_type.fuel(), 
-            size : size
+            size : size,
+            weapon_type : launch.weapon_type,
+            source : launch.parent,
+            owner : owner
         }
     ).id()
 }
@@ -127,30 +310,186 @@ pub fn launching_system(
     mut events: EventReader<Launch>,
     mut commands: Commands,
     transform_query: Query<&GlobalTransform>,
-    materials : Res<WeaponMaterials>
+    owner_query: Query<&BaseOwner>,
+    mut active_weapons : ResMut<ActiveWeapons>
 ) {
     for launch_event in events.iter() {
         if let Ok(parent_transform) = transform_query.get(launch_event.parent) { 
-            launch_weapon(
-                &mut commands, 
-                parent_transform, 
-                &materials, 
-                launch_event
-            );
+            let owner = owner_query.get(launch_event.parent).ok().map(|owner| owner.entity);
+            // Fan the volley evenly across the spread centred on the aim
+            let count = launch_event.weapon_type.count();
+            let spread = launch_event.weapon_type.spread();
+            for i in 0..count {
+                let fraction = if count > 1 { 
+                    (i as f32) / ((count - 1) as f32) - 0.5 
+                } else { 
+                    0.0 
+                };
+                let weapon = launch_weapon(
+                    &mut commands, 
+                    parent_transform, 
+                    launch_event,
+                    owner,
+                    launch_event.angle + fraction * spread
+                );
+                active_weapons.add(weapon);
+                if launch_event.weapon_type == WeaponType::HomingRocket {
+                    if let Some(owner) = owner {
+                        commands.entity(weapon).insert(Homing { 
+                            owner : owner, 
+                            turn_rate : 1.2 
+                        });
+                    }
+                }
+            }
         }
     }
 }
 
+// Give new weapons their sprite
+fn weapon_added(
+    mut commands: Commands,
+    weapon_query: Query<(Entity, &Weapon), Added<Weapon>>,
+    materials : Res<WeaponMaterials>
+) {
+    for (entity, weapon) in weapon_query.iter() {
+        commands.entity(entity).insert(Sprite { 
+            custom_size : Some(weapon.size), 
+            ..Default::default()
+        }).insert(materials.texture(weapon.weapon_type)
+        ).insert(Visibility::default());
+    }
+}
+
+// Pull weapons in flight with gravity and move them on, boring weapons go straight
+// through the rock and weapons which have hit something wait where they are
 pub fn weapon_move_update(
-    mut weapon_query: Query<(&Weapon, &mut Transform)>, 
-    time: Res<Time>
+    mut weapon_query: Query<(&mut Weapon, &mut Transform, Option<&Boring>, Option<&Impact>), Without<GravitySource>>, 
+    source_query : Query<(&GravitySource, &Transform)>
 ) {
-    for (weapon, mut transform) in weapon_query.iter_mut() {
-        let thrust = Vec3::new(weapon.thrust.x, weapon.thrust.y, 0.0);
+    let bodies = gravity_bodies(&source_query);
+    for (mut weapon, mut transform, boring, impact) in weapon_query.iter_mut() {
+        if impact.is_some() && boring.is_none() {
+            continue;
+        }
+        let pos = Vec2::new(transform.translation.x, transform.translation.y);
+        let gravity = match boring {
+            Some(_) => Vec2::ZERO,
+            None => body_gravity(&bodies, pos, PHYSICS_STEP)
+        };
+        let (pos, thrust) = flight_step(pos, weapon.thrust, gravity);
+        weapon.thrust = thrust;
+        transform.translation.x = pos.x;
+        transform.translation.y = pos.y;
         if thrust.length() > 1.0 {
-            let goal_rotation = Quat::from_rotation_arc(Vec3::new(0.0,1.0,0.0), thrust.normalize());
+            let direction = Vec3::new(thrust.x, thrust.y, 0.0).normalize();
+            let goal_rotation = Quat::from_rotation_arc(Vec3::new(0.0,1.0,0.0), direction);
             transform.rotation = transform.rotation.lerp(goal_rotation, 0.3);
-            transform.translation += thrust * time.delta_seconds();
+        }
+    }
+}
+
+// Turn the thrust of homing weapons towards their target, limited by the turn rate
+// so that the gravity from asteroids can still pull them off course
+pub fn weapon_homing_update(
+    mut weapon_query: Query<(&mut Weapon, &Homing, &Transform)>,
+    base_query: Query<(&BaseOwner, &Transform, &Parent), With<Base>>,
+    asteroid_query: Query<&Transform, With<Asteroid>>,
+    player_query: Query<&Player>
+) {
+    for (mut weapon, homing, transform) in weapon_query.iter_mut() {
+        let pos = Vec2::new(transform.translation.x, transform.translation.y);
+        // Bases sit on asteroids, which may have moved since the last frame.
+        // Team mates' bases are never targets, even with friendly fire on
+        let target = base_query.iter().filter(
+            |(owner, _, _)| owner.entity != homing.owner && !team_mates(&player_query, homing.owner, owner.entity)
+        ).filter_map(
+            |(_, base_transform, parent)| asteroid_query.get(parent.0).ok().map(
+                |asteroid_transform| {
+                    let base_pos = asteroid_transform.translation + base_transform.translation;
+                    Vec2::new(base_pos.x, base_pos.y)
+                }
+            )
+        ).min_by(
+            |a, b| a.distance_squared(pos).partial_cmp(&b.distance_squared(pos)).unwrap()
+        );
+        if let Some(target) = target {
+            let desired = target - pos;
+            if weapon.thrust.length() > 1.0 && desired.length() > 1.0 {
+                let max_turn = homing.turn_rate * PHYSICS_STEP;
+                let turn = weapon.thrust.angle_between(desired).clamp(-max_turn, max_turn);
+                let thrust = Quat::from_rotation_z(turn) * Vec3::new(weapon.thrust.x, weapon.thrust.y, 0.0);
+                weapon.thrust = Vec2::new(thrust.x, thrust.y);
+            }
+        }
+    }
+}
+
+// Start a weapon boring into the asteroid it hit
+pub fn weapon_bore_start(
+    mut events: EventReader<WeaponBore>,
+    mut commands: Commands,
+    mut weapon_query: Query<&mut Weapon, Without<Boring>>
+) {
+    let bore_time = 0.8;
+    for event in events.iter() {
+        if let Ok(mut weapon) = weapon_query.get_mut(event.entity) {
+            // Make sure the weapon does not run out of fuel before it detonates
+            weapon.fuel = weapon.fuel.max(bore_time + 1.0);
+            commands.entity(event.entity).insert(Boring { 
+                asteroid : event.asteroid, 
+                time : bore_time 
+            });
+        }
+    }
+}
+
+pub fn weapon_bore_update(
+    mut weapon_query: Query<(Entity, &mut Weapon, &mut Boring, &Transform)>,
+    asteroid_query: Query<&Transform, With<Asteroid>>,
+    mut events: EventWriter<WeaponExplode>
+) {
+    let bore_speed = 30.0;
+    for (entity, mut weapon, mut boring, transform) in weapon_query.iter_mut() {
+        boring.time -= PHYSICS_STEP;
+        match asteroid_query.get(boring.asteroid) {
+            Ok(asteroid_transform) if boring.time > 0.0 => {
+                let delta = asteroid_transform.translation - transform.translation;
+                let direction = Vec2::new(delta.x, delta.y).normalize_or_zero();
+                weapon.thrust = direction * bore_speed;
+            },
+            _ => {
+                weapon.thrust = Vec2::ZERO;
+                events.send(WeaponExplode { entity : entity });
+            }
+        }
+    }
+}
+
+// Pull the asteroid a yank weapon hit towards the base that fired it
+pub fn weapon_yank(
+    mut events: EventReader<WeaponYank>,
+    weapon_query: Query<(&Weapon, &GlobalTransform)>,
+    transform_query: Query<&GlobalTransform>,
+    mut events_impulse: EventWriter<AsteroidImpulse>,
+    mut events_explode: EventWriter<WeaponExplode>
+) {
+    // Gives an asteroid of radius 50 a speed of 40
+    let yank_impulse = 1.5e7;
+    for event in events.iter() {
+        if let Ok((weapon, weapon_transform)) = weapon_query.get(event.entity) {
+            let source_pos = transform_query.get(weapon.source).map(
+                |transform| transform.translation
+            ).unwrap_or(weapon_transform.translation);
+            if let Ok(asteroid_transform) = transform_query.get(event.asteroid) {
+                let delta = source_pos - asteroid_transform.translation;
+                let direction = Vec2::new(delta.x, delta.y).normalize_or_zero();
+                events_impulse.send(AsteroidImpulse { 
+                    asteroid : event.asteroid, 
+                    impulse : direction * yank_impulse 
+                });
+            }
+            events_explode.send(WeaponExplode { entity : event.entity });
         }
     }
 }
@@ -158,15 +497,32 @@ pub fn weapon_move_update(
 pub fn weapon_explode(
     mut events: EventReader<WeaponExplode>,
     mut commands: Commands,
-    weapon_query : Query<&GlobalTransform>,
+    weapon_query : Query<(&GlobalTransform, &Weapon)>,
+    mut active_weapons : ResMut<ActiveWeapons>,
     mut events_turn : EventWriter<TurnEnd>,
-    mut events_explosion : EventWriter<Explode>
+    mut events_explosion : EventWriter<Explode>,
+    mut events_well : EventWriter<SpawnGravityWell>
 ) {
     for event in events.iter() {
-        if let Ok(transform) = weapon_query.get(event.entity) { 
-            events_turn.send(TurnEnd);
+        if let Ok((transform, weapon)) = weapon_query.get(event.entity) { 
+            if !active_weapons.retire(event.entity, &mut events_turn) {
+                continue;
+            }
             let pos = transform.translation;
-            events_explosion.send(Explode { pos : Vec2::new(pos.x, pos.y), power : 50.0 } );
+            events_explosion.send(Explode { 
+                pos : Vec2::new(pos.x, pos.y), 
+                power : weapon.weapon_type.power(),
+                asteroid_multiplier : weapon.weapon_type.asteroid_multiplier(),
+                source : weapon.owner
+            });
+            let well_type = match weapon.weapon_type {
+                WeaponType::BlackHole => Some(GravityWellType::BlackHole),
+                WeaponType::WhiteHole => Some(GravityWellType::WhiteHole),
+                _ => None
+            };
+            if let Some(well_type) = well_type {
+                events_well.send(SpawnGravityWell { pos : Vec2::new(pos.x, pos.y), well_type : well_type });
+            }
             commands.entity(event.entity).despawn_recursive();
         }
     }
@@ -174,11 +530,13 @@ pub fn weapon_explode(
 
 fn teardown_weapons(
     mut commands : Commands,
-    weapon_query : Query<Entity, With<Weapon>>
+    weapon_query : Query<Entity, With<Weapon>>,
+    mut active_weapons : ResMut<ActiveWeapons>
 ) {
     for weapon in weapon_query.iter() {
         commands.entity(weapon).despawn_recursive();
     }
+    active_weapons.weapons.clear();
 }
 
 // Plugins
@@ -188,16 +546,37 @@ impl Plugin for WeaponPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<Launch>()
            .add_event::<WeaponExplode>()
-           .init_resource::<WeaponMaterials>()
+           .add_event::<WeaponBore>()
+           .add_event::<WeaponYank>()
+           .init_resource::<ActiveWeapons>()
            .add_system_set(
              SystemSet::on_update(AppState::InGame)
                .with_system(launching_system.system())
-               .with_system(weapon_move_update.system())
-               .with_system(weapon_fuel_update.system())
+               .with_system(weapon_bore_start.system())
+               .with_system(weapon_yank.system())
                .with_system(weapon_explode.system()))
+           .add_system_set_to_stage(
+             PhysicsStage,
+             SystemSet::new()
+               .with_system(weapon_homing_update.system().label(PhysicsLabel::Steer))
+               .with_system(weapon_bore_update.system().label(PhysicsLabel::Steer))
+               .with_system(weapon_move_update.system().label(PhysicsLabel::Move).after(PhysicsLabel::Steer))
+               .with_system(weapon_fuel_update.system().after(PhysicsLabel::Move)))
            .add_system_set(
               SystemSet::on_exit(AppState::InGame)
                .with_system(teardown_weapons.system())
             );
     }
-}
\ No newline at end of file
+}
+
+pub struct WeaponRenderPlugin;
+
+impl Plugin for WeaponRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WeaponMaterials>()
+           .add_system_set(
+             SystemSet::on_update(AppState::InGame)
+               .with_system(weapon_added.system())
+           );
+    }
+}
@@ -0,0 +1,158 @@
+// Computer players finding shots and playing their turns
+
+mod common;
+
+use bevy::prelude::*;
+
+use rasteroids_game::ai::{AiSettings, AiTurn, Controller, Difficulty, ShotProblem};
+use rasteroids_game::base::BaseOwner;
+use rasteroids_game::inventory::Inventory;
+use rasteroids_game::player::PlayerSettings;
+use rasteroids_game::turn::{TurnState, TurnPhase, TurnEnd};
+use rasteroids_game::weapon::WeaponType;
+
+use common::*;
+
+// Duel where the second player is the computer
+fn computer_duel_with(difficulty : Difficulty, ai_settings : AiSettings) -> App {
+    let mut settings = PlayerSettings::default();
+    settings.players[1].controller = Controller::Computer(difficulty);
+    let mut app = match_app(DUEL_MAP);
+    app.insert_resource(settings);
+    app.insert_resource(ai_settings);
+    begin(&mut app);
+    app
+}
+
+// Computer acting as soon as its turn starts
+fn computer_duel(difficulty : Difficulty) -> App {
+    computer_duel_with(difficulty, AiSettings { think_time : 0.0, seed : Some(1) })
+}
+
+fn active_owner(app : &App) -> Entity {
+    let base = app.world.get_resource::<TurnState>().unwrap().active_base.unwrap();
+    app.world.get::<BaseOwner>(base).unwrap().entity
+}
+
+fn inventory(app : &App, index : usize) -> Inventory {
+    app.world.get::<Inventory>(player(app, index)).unwrap().clone()
+}
+
+#[test]
+fn search_finds_a_shot_through_empty_space() {
+    let base = GlobalTransform::identity();
+    let problem = ShotProblem {
+        base : &base,
+        bodies : Vec::new(),
+        rocks : Vec::new(),
+        targets : vec![Vec2::new(300.0, 412.0)],
+        friends : vec![Vec2::ZERO],
+        fuel : 10.0
+    };
+    let shot = problem.search(Difficulty::Hard.budget()).unwrap();
+    assert!(shot.miss < 5.0);
+    // Off to the right of straight up
+    assert!(shot.angle < 0.0);
+}
+
+#[test]
+fn search_without_targets_finds_nothing() {
+    let base = GlobalTransform::identity();
+    let problem = ShotProblem {
+        base : &base,
+        bodies : Vec::new(),
+        rocks : Vec::new(),
+        targets : Vec::new(),
+        friends : vec![Vec2::ZERO],
+        fuel : 10.0
+    };
+    assert!(problem.search(Difficulty::Hard.budget()).is_none());
+}
+
+#[test]
+fn computer_leaves_human_turns_alone() {
+    let mut app = computer_duel(Difficulty::Hard);
+    let first = app.world.get_resource::<TurnState>().unwrap().active_base;
+    step(&mut app, 10);
+    assert_eq!(app.world.get_resource::<TurnState>().unwrap().active_base, first);
+    assert_eq!(inventory(&app, 0).count(WeaponType::Rocket), 20);
+}
+
+#[test]
+fn hard_computer_hits_an_enemy_base() {
+    let mut app = computer_duel(Difficulty::Hard);
+    send(&mut app, TurnEnd);
+    step(&mut app, 5);
+    assert_eq!(inventory(&app, 1).count(WeaponType::Rocket), 19);
+    // Give the shot time to land
+    step(&mut app, 600);
+    assert_eq!(active_owner(&app), player(&app, 0));
+    let bases = bases_of(&mut app, 0);
+    assert!(bases.iter().any(|base| base_health(&app, *base) < 100.0));
+}
+
+#[test]
+fn computer_with_nothing_to_fire_passes_the_turn() {
+    let mut app = computer_duel(Difficulty::Normal);
+    let computer = player(&app, 1);
+    {
+        let mut inventory = app.world.get_mut::<Inventory>(computer).unwrap();
+        for weapon_type in WeaponType::ALL {
+            while inventory.take(weapon_type) {}
+        }
+    }
+    send(&mut app, TurnEnd);
+    step(&mut app, 10);
+    assert_eq!(active_owner(&app), player(&app, 0));
+    assert_eq!(app.world.get_resource::<TurnState>().unwrap().phase, TurnPhase::Aiming);
+    // The next player is free to act
+    assert!(!app.world.get_resource::<AiTurn>().unwrap().active());
+}
+
+#[test]
+fn computer_falls_back_on_other_rockets_in_its_inventory() {
+    let mut app = computer_duel(Difficulty::Hard);
+    let computer = player(&app, 1);
+    {
+        let mut inventory = app.world.get_mut::<Inventory>(computer).unwrap();
+        for weapon_type in [WeaponType::Rocket, WeaponType::MultiRocket] {
+            while inventory.take(weapon_type) {}
+        }
+    }
+    send(&mut app, TurnEnd);
+    step(&mut app, 5);
+    assert_eq!(inventory(&app, 1).count(WeaponType::HomingRocket), 1);
+}
+
+#[test]
+fn computer_keeps_back_weapons_which_do_not_go_off_on_impact() {
+    let mut app = computer_duel(Difficulty::Hard);
+    let computer = player(&app, 1);
+    {
+        let mut inventory = app.world.get_mut::<Inventory>(computer).unwrap();
+        for weapon_type in WeaponType::ALL.iter().filter(|weapon_type| weapon_type.explodes_on_impact()) {
+            while inventory.take(*weapon_type) {}
+        }
+    }
+    let before = inventory(&app, 1);
+    send(&mut app, TurnEnd);
+    step(&mut app, 10);
+    assert_eq!(active_owner(&app), player(&app, 0));
+    let after = inventory(&app, 1);
+    assert!(WeaponType::ALL.iter().all(
+        |weapon_type| after.count(*weapon_type) == before.count(*weapon_type)
+    ));
+}
+
+#[test]
+fn same_seed_gives_the_same_shaky_aim() {
+    let aims : Vec<(f32, f32)> = (0..2).map(|_| {
+        // Long enough thinking that the aim can be read before the shot
+        let mut app = computer_duel_with(Difficulty::Easy, AiSettings { think_time : 1000.0, seed : Some(7) });
+        send(&mut app, TurnEnd);
+        step(&mut app, 3);
+        let turn_state = app.world.get_resource::<TurnState>().unwrap();
+        (turn_state.firing_angle, turn_state.power)
+    }).collect();
+    assert_eq!(aims[0], aims[1]);
+}
@@ -0,0 +1,108 @@
+// Helpers for driving matches in a headless app
+
+#![allow(dead_code)]
+
+use bevy::prelude::*;
+use bevy::app::Events;
+
+use rasteroids_game::headless_app;
+use rasteroids_game::app_state::AppState;
+use rasteroids_game::asteroids::Asteroid;
+use rasteroids_game::base::{Base, BaseOwner};
+use rasteroids_game::map::MapChoice;
+use rasteroids_game::map_gen::MapSettings;
+use rasteroids_game::physics::{PhysicsClock, PHYSICS_STEP};
+use rasteroids_game::player::PlayerOrder;
+use rasteroids_game::weapon::{Launch, WeaponType};
+
+pub const DUEL_MAP : &str = "tests/maps/duel.ron";
+
+// App for a match on a map file, with the physics stepped by the test instead of the clock.
+// Settings can be changed before calling begin
+pub fn match_app(map : &str) -> App {
+    let mut app = headless_app();
+    app.insert_resource(MapChoice::File(map.to_string()));
+    app.world.get_resource_mut::<PhysicsClock>().unwrap().manual = true;
+    app
+}
+
+// App for a match on a map generated from a seed
+pub fn generated_app(seed : u64) -> App {
+    let mut app = headless_app();
+    app.insert_resource(MapSettings { seed : Some(seed), ..Default::default() });
+    app.world.get_resource_mut::<PhysicsClock>().unwrap().manual = true;
+    app
+}
+
+pub fn begin(app : &mut App) {
+    app.world.get_resource_mut::<State<AppState>>().unwrap().set(AppState::InGame).unwrap();
+    // Spawn the map then let the first turn start
+    step(app, 2);
+}
+
+pub fn start_match(map : &str) -> App {
+    let mut app = match_app(map);
+    begin(&mut app);
+    app
+}
+
+// Run frames with one physics step in each
+pub fn step(app : &mut App, frames : usize) {
+    for _ in 0..frames {
+        app.world.get_resource_mut::<PhysicsClock>().unwrap().advance(PHYSICS_STEP);
+        app.update();
+    }
+}
+
+pub fn send<T : Send + Sync + 'static>(app : &mut App, event : T) {
+    app.world.get_resource_mut::<Events<T>>().unwrap().send(event);
+}
+
+// Fire a weapon from a base, the angle is relative to the way the base is facing
+pub fn launch(app : &mut App, base : Entity, angle : f32, thrust : f32, weapon_type : WeaponType) {
+    send(app, Launch {
+        angle : angle,
+        offset : 12.0,
+        thrust : thrust,
+        parent : base,
+        weapon_type : weapon_type
+    });
+}
+
+pub fn player(app : &App, index : usize) -> Entity {
+    app.world.get_resource::<PlayerOrder>().unwrap().order[index]
+}
+
+pub fn state(app : &App) -> AppState {
+    app.world.get_resource::<State<AppState>>().unwrap().current().clone()
+}
+
+// Bases owned by the player in the given turn order slot, in the order they were spawned
+pub fn bases_of(app : &mut App, index : usize) -> Vec<Entity> {
+    let owner = player(app, index);
+    let mut bases : Vec<Entity> = app.world.query::<(Entity, &BaseOwner)>().iter(&app.world).filter(
+        |(_, base_owner)| base_owner.entity == owner
+    ).map(
+        |(entity, _)| entity
+    ).collect();
+    bases.sort();
+    bases
+}
+
+pub fn base_health(app : &App, base : Entity) -> f32 {
+    app.world.get::<Base>(base).unwrap().health
+}
+
+// Asteroid a base is standing on
+pub fn asteroid_under(app : &App, base : Entity) -> Entity {
+    app.world.get::<Parent>(base).unwrap().0
+}
+
+pub fn asteroid(app : &App, asteroid : Entity) -> Asteroid {
+    app.world.get::<Asteroid>(asteroid).unwrap().clone()
+}
+
+pub fn position(app : &App, entity : Entity) -> Vec2 {
+    let transform = app.world.get::<GlobalTransform>(entity).unwrap();
+    Vec2::new(transform.translation.x, transform.translation.y)
+}
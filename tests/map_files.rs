@@ -0,0 +1,90 @@
+// Reading map files and pointing at the line with the mistake when they are wrong
+
+use rasteroids_game::map::{parse_map, MapError};
+
+const MAP : &str = "\
+(
+    players: 2,
+    asteroids: [
+        (
+            x: -300.0,
+            y: 0.0,
+            radius: 60.0,
+            bases: [(angle: 0.0, player: 0)],
+        ),
+        (
+            x: 300.0,
+            y: 0.0,
+            radius: 60.0,
+            // A moon
+            texture: \"images/moon.png\",
+            bases: [
+                (angle: 0.0, player: 1),
+                (angle: 3.0, player: 1),
+            ],
+        ),
+    ],
+)
+";
+
+fn parse(text : &str) -> Result<(), MapError> {
+    parse_map("test.ron", text).map(|_| ())
+}
+
+#[test]
+fn good_map_parses() {
+    let layout = parse_map("test.ron", MAP).unwrap();
+    assert_eq!(layout.players, 2);
+    assert_eq!(layout.asteroids.len(), 2);
+    assert_eq!(layout.asteroids[1].bases.len(), 2);
+    assert_eq!(layout.asteroids[0].density, 1.0);
+}
+
+#[test]
+fn missing_field_is_named() {
+    let error = parse(&MAP.replace("    players: 2,\n", "")).unwrap_err();
+    assert!(matches!(error, MapError::Parse { .. }));
+    assert!(error.to_string().contains("missing field `players`"));
+}
+
+#[test]
+fn wrong_type_gives_the_field_and_line() {
+    let error = parse(&MAP.replacen("radius: 60.0", "radius: \"big\"", 1)).unwrap_err();
+    match error {
+        MapError::Parse { field, line, .. } => {
+            assert_eq!(field, "asteroids[0].radius");
+            assert_eq!(line, 7);
+        },
+        error => panic!("expected a parse error, got {}", error)
+    }
+}
+
+#[test]
+fn invalid_value_gives_the_field_and_line() {
+    let error = parse(&MAP.replacen("radius: 60.0", "radius: -5.0", 2).replacen("radius: -5.0", "radius: 60.0", 1)).unwrap_err();
+    assert_eq!(error.to_string(), "test.ron:13: invalid field `asteroids[1].radius`: radius must be positive, found -5");
+}
+
+#[test]
+fn invalid_value_in_a_list_gives_the_line_of_the_entry() {
+    let error = parse(&MAP.replace("(angle: 3.0, player: 1)", "(angle: 3.0, player: 4)")).unwrap_err();
+    match error {
+        MapError::Invalid { field, line, .. } => {
+            assert_eq!(field, "asteroids[1].bases[1].player");
+            assert_eq!(line, 18);
+        },
+        error => panic!("expected an invalid field error, got {}", error)
+    }
+}
+
+#[test]
+fn invalid_player_count_is_found_at_the_top() {
+    let error = parse(&MAP.replace("players: 2", "players: 0")).unwrap_err();
+    match error {
+        MapError::Invalid { field, line, .. } => {
+            assert_eq!(field, "players");
+            assert_eq!(line, 2);
+        },
+        error => panic!("expected an invalid field error, got {}", error)
+    }
+}
@@ -0,0 +1,198 @@
+// Whole matches played without a window, driven by scripted launches and explosions
+
+mod common;
+
+use bevy::prelude::*;
+
+use std::f32::consts::PI;
+
+use rasteroids_game::app_state::AppState;
+use rasteroids_game::base::Base;
+use rasteroids_game::explosion::Explode;
+use rasteroids_game::game::TurnAction;
+use rasteroids_game::player::{Player, Victory};
+use rasteroids_game::turn::{TurnState, TurnPhase, TurnEnd};
+use rasteroids_game::weapon::WeaponType;
+
+use common::*;
+
+fn active_base(app : &App) -> Option<Entity> {
+    app.world.get_resource::<TurnState>().unwrap().active_base
+}
+
+fn player_name(app : &App, index : usize) -> String {
+    app.world.get::<Player>(player(app, index)).unwrap().name.clone()
+}
+
+// Explosion big enough to destroy any base it is dropped on, without touching the rock
+fn destroy(app : &mut App, base : Entity) {
+    let pos = position(app, base);
+    send(app, Explode { pos : pos, power : 1000.0, asteroid_multiplier : 0.0, source : None });
+}
+
+#[test]
+fn map_spawns_bases_for_each_player() {
+    let mut app = start_match(DUEL_MAP);
+    assert_eq!(state(&app), AppState::InGame);
+    assert_eq!(bases_of(&mut app, 0).len(), 2);
+    assert_eq!(bases_of(&mut app, 1).len(), 1);
+    assert_eq!(app.world.query::<&Base>().iter(&app.world).count(), 3);
+}
+
+#[test]
+fn first_turn_goes_to_first_base_of_first_player() {
+    let mut app = start_match(DUEL_MAP);
+    let bases = bases_of(&mut app, 0);
+    assert_eq!(active_base(&app), Some(bases[0]));
+}
+
+#[test]
+fn turns_rotate_between_players_and_their_bases() {
+    let mut app = start_match(DUEL_MAP);
+    let bases_0 = bases_of(&mut app, 0);
+    let bases_1 = bases_of(&mut app, 1);
+    let expected = [bases_1[0], bases_0[1], bases_1[0], bases_0[0]];
+    for base in expected {
+        send(&mut app, TurnEnd);
+        step(&mut app, 2);
+        assert_eq!(active_base(&app), Some(base));
+    }
+}
+
+#[test]
+fn rocket_into_the_ground_craters_the_asteroid_and_hurts_the_base() {
+    let mut app = start_match(DUEL_MAP);
+    let base = bases_of(&mut app, 0)[0];
+    let rock = asteroid_under(&app, base);
+    let before = asteroid(&app, rock);
+    // Straight down into the asteroid the base is standing on
+    launch(&mut app, base, PI, 70.0, WeaponType::Rocket);
+    step(&mut app, 10);
+    let after = asteroid(&app, rock);
+    assert!(after.height(0.0) < before.height(0.0));
+    assert!(after.terrain.equivalent_radius() < before.terrain.equivalent_radius());
+    assert!(after.mass() < before.mass());
+    assert!(base_health(&app, base) < 100.0);
+    // The base on the far side of the asteroid is out of range
+    let far_base = bases_of(&mut app, 0)[1];
+    assert_eq!(base_health(&app, far_base), 100.0);
+    assert_eq!(after.height(PI), before.height(PI));
+}
+
+#[test]
+fn turn_passes_once_the_shot_has_landed() {
+    let mut app = start_match(DUEL_MAP);
+    let base = bases_of(&mut app, 0)[0];
+    launch(&mut app, base, PI, 70.0, WeaponType::Rocket);
+    step(&mut app, 10);
+    let other = bases_of(&mut app, 1)[0];
+    assert_eq!(active_base(&app), Some(other));
+}
+
+#[test]
+fn volley_passes_the_turn_once() {
+    let mut app = start_match(DUEL_MAP);
+    let base = bases_of(&mut app, 0)[0];
+    let next_base = bases_of(&mut app, 0)[1];
+    launch(&mut app, base, PI, 70.0, WeaponType::MultiRocket);
+    step(&mut app, 10);
+    // All three rockets have landed but only one turn has gone by, so the first player
+    // moves on to their other base next
+    let other = bases_of(&mut app, 1)[0];
+    assert_eq!(active_base(&app), Some(other));
+    send(&mut app, TurnEnd);
+    step(&mut app, 2);
+    assert_eq!(active_base(&app), Some(next_base));
+}
+
+#[test]
+fn explosion_damage_falls_off_with_distance() {
+    let mut app = start_match(DUEL_MAP);
+    let base = bases_of(&mut app, 1)[0];
+    let pos = position(&app, base);
+    send(&mut app, Explode { pos : pos + Vec2::new(0.0, 30.0), power : 40.0, asteroid_multiplier : 0.0, source : None });
+    step(&mut app, 2);
+    let near = 100.0 - base_health(&app, base);
+    send(&mut app, Explode { pos : pos + Vec2::new(0.0, 40.0), power : 40.0, asteroid_multiplier : 0.0, source : None });
+    step(&mut app, 2);
+    let far = 100.0 - base_health(&app, base) - near;
+    assert!(near > far);
+    assert!(far > 0.0);
+}
+
+#[test]
+fn losing_the_last_base_gives_victory_to_the_other_player() {
+    let mut app = start_match(DUEL_MAP);
+    let winner = player_name(&app, 0);
+    let base = bases_of(&mut app, 1)[0];
+    destroy(&mut app, base);
+    step(&mut app, 3);
+    assert_eq!(state(&app), AppState::VictoryMenu);
+    let victory = app.world.get_resource::<Victory>().unwrap();
+    let members : Vec<String> = victory.members.iter().map(|player| player.name.clone()).collect();
+    assert_eq!(members, vec![winner]);
+    assert!(victory.team.is_some());
+}
+
+#[test]
+fn losing_one_of_several_bases_keeps_the_match_going() {
+    let mut app = start_match(DUEL_MAP);
+    let base = bases_of(&mut app, 0)[1];
+    destroy(&mut app, base);
+    step(&mut app, 3);
+    assert_eq!(state(&app), AppState::InGame);
+    assert_eq!(bases_of(&mut app, 0).len(), 1);
+}
+
+#[test]
+fn every_base_lost_at_once_is_a_draw() {
+    let mut app = start_match(DUEL_MAP);
+    let mut bases = bases_of(&mut app, 0);
+    bases.extend(bases_of(&mut app, 1));
+    for base in bases {
+        destroy(&mut app, base);
+    }
+    step(&mut app, 3);
+    assert_eq!(state(&app), AppState::VictoryMenu);
+    let victory = app.world.get_resource::<Victory>().unwrap();
+    assert!(victory.team.is_none());
+    assert!(victory.members.is_empty());
+}
+
+#[test]
+fn turn_ending_as_a_player_loses_their_last_base_does_not_panic() {
+    let mut app = start_match(DUEL_MAP);
+    let base = bases_of(&mut app, 1)[0];
+    destroy(&mut app, base);
+    send(&mut app, TurnEnd);
+    step(&mut app, 3);
+    assert_eq!(state(&app), AppState::VictoryMenu);
+}
+
+#[test]
+fn base_destroyed_while_repairing_passes_the_turn() {
+    let mut app = start_match(DUEL_MAP);
+    let base = bases_of(&mut app, 0)[0];
+    let other = bases_of(&mut app, 1)[0];
+    send(&mut app, TurnAction::Repair);
+    step(&mut app, 2);
+    destroy(&mut app, base);
+    step(&mut app, 3);
+    assert_eq!(state(&app), AppState::InGame);
+    assert_eq!(active_base(&app), Some(other));
+    assert_eq!(app.world.get_resource::<TurnState>().unwrap().phase, TurnPhase::Aiming);
+}
+
+#[test]
+fn same_shot_gives_the_same_crater() {
+    let craters : Vec<Vec<f32>> = (0..2).map(|_| {
+        let mut app = start_match(DUEL_MAP);
+        let base = bases_of(&mut app, 0)[0];
+        let rock = asteroid_under(&app, base);
+        launch(&mut app, base, PI - 0.3, 70.0, WeaponType::Rocket);
+        step(&mut app, 20);
+        let terrain = asteroid(&app, rock).terrain;
+        (0..terrain.samples()).map(|index| terrain.sample_height(index)).collect()
+    }).collect();
+    assert_eq!(craters[0], craters[1]);
+}
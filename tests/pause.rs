@@ -0,0 +1,78 @@
+// Pausing part way through a shot and restarting a match
+
+mod common;
+
+use bevy::prelude::*;
+
+use std::f32::consts::PI;
+
+use rasteroids_game::app_state::AppState;
+use rasteroids_game::base::Base;
+use rasteroids_game::turn::{TurnState, TurnPhase};
+use rasteroids_game::weapon::{Weapon, WeaponType};
+
+use common::*;
+
+fn weapon(app : &mut App) -> (Vec2, f32) {
+    let (weapon, transform) = app.world.query::<(&Weapon, &Transform)>().iter(&app.world).next().unwrap();
+    (Vec2::new(transform.translation.x, transform.translation.y), weapon.fuel)
+}
+
+fn turn_state(app : &mut App) -> Mut<'_, TurnState> {
+    app.world.get_resource_mut::<TurnState>().unwrap()
+}
+
+#[test]
+fn pausing_freezes_weapons_in_flight() {
+    let mut app = start_match(DUEL_MAP);
+    let base = bases_of(&mut app, 0)[0];
+    // Straight up and away from everything
+    launch(&mut app, base, 0.0, 70.0, WeaponType::Rocket);
+    step(&mut app, 5);
+    turn_state(&mut app).pause();
+    let frozen = weapon(&mut app);
+    step(&mut app, 20);
+    assert_eq!(weapon(&mut app), frozen);
+    turn_state(&mut app).resume();
+    step(&mut app, 5);
+    let (pos, fuel) = weapon(&mut app);
+    assert!(pos.y > frozen.0.y);
+    assert!(fuel < frozen.1);
+}
+
+#[test]
+fn resume_goes_back_to_the_phase_paused_in() {
+    let mut app = start_match(DUEL_MAP);
+    turn_state(&mut app).pause();
+    step(&mut app, 2);
+    assert_eq!(turn_state(&mut app).phase, TurnPhase::Paused);
+    turn_state(&mut app).resume();
+    assert_eq!(turn_state(&mut app).phase, TurnPhase::Aiming);
+}
+
+#[test]
+fn restart_puts_the_map_back_as_it_started() {
+    let mut app = start_match(DUEL_MAP);
+    let base = bases_of(&mut app, 0)[0];
+    let rock = asteroid_under(&app, base);
+    let start = asteroid(&app, rock).terrain.area();
+    launch(&mut app, base, PI, 70.0, WeaponType::Rocket);
+    step(&mut app, 10);
+    assert!(asteroid(&app, rock).terrain.area() < start);
+    turn_state(&mut app).pause();
+    app.world.get_resource_mut::<State<AppState>>().unwrap().set(AppState::Restarting).unwrap();
+    step(&mut app, 3);
+    assert_eq!(state(&app), AppState::InGame);
+    assert!(!turn_state(&mut app).paused());
+    let bases = bases_of(&mut app, 0);
+    assert_eq!(bases.len(), 2);
+    assert_eq!(bases_of(&mut app, 1).len(), 1);
+    for base in bases.iter() {
+        assert_eq!(base_health(&app, *base), 100.0);
+        assert_eq!(asteroid(&app, asteroid_under(&app, *base)).terrain.area(), start);
+    }
+    // Back to the first turn of the match
+    let active = turn_state(&mut app).active_base.unwrap();
+    assert!(bases.contains(&active));
+    assert_eq!(app.world.get::<Base>(active).unwrap().angle, 0.0);
+}
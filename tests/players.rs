@@ -0,0 +1,129 @@
+// Choosing who plays before a match
+
+mod common;
+
+use bevy::prelude::*;
+
+use rasteroids_game::base::{Base, BaseOwner};
+use rasteroids_game::map_gen::{generate_map, MapSettings};
+use rasteroids_game::player::{Player, PlayerSettings, MIN_PLAYERS, MAX_PLAYERS};
+use rasteroids_game::turn::{TurnState, TurnEnd};
+
+use common::*;
+
+fn players(count : usize) -> PlayerSettings {
+    let mut settings = PlayerSettings::default();
+    while settings.players.len() < count {
+        settings.add();
+    }
+    settings
+}
+
+#[test]
+fn players_can_be_added_up_to_the_limit() {
+    let mut settings = PlayerSettings::default();
+    assert_eq!(settings.players.len(), MIN_PLAYERS);
+    while settings.add() {}
+    assert_eq!(settings.players.len(), MAX_PLAYERS);
+    // Everyone ends up with their own colour
+    for (index, player) in settings.players.iter().enumerate() {
+        assert!(settings.players[index + 1..].iter().all(|other| other.colour != player.colour));
+    }
+}
+
+#[test]
+fn players_can_not_be_removed_below_the_minimum() {
+    let mut settings = players(3);
+    assert!(settings.remove(0));
+    assert!(!settings.remove(0));
+    assert_eq!(settings.players.len(), MIN_PLAYERS);
+}
+
+#[test]
+fn colour_changes_skip_colours_in_use() {
+    let mut settings = players(3);
+    let taken : Vec<Color> = settings.players.iter().map(|player| player.colour).collect();
+    settings.next_colour(0);
+    let colour = settings.players[0].colour;
+    assert!(!taken.contains(&colour));
+}
+
+#[test]
+fn configured_players_join_the_match_in_order() {
+    let mut settings = players(4);
+    settings.players[2].name = "Ada".to_string();
+    let mut app = generated_app(11);
+    app.insert_resource(settings);
+    begin(&mut app);
+    let names : Vec<String> = (0..4).map(
+        |index| app.world.get::<Player>(player(&app, index)).unwrap().name.clone()
+    ).collect();
+    assert_eq!(names, ["Robert", "James", "Ada", "Player 4"]);
+}
+
+#[test]
+fn generated_map_gives_every_player_bases_and_a_turn() {
+    let mut app = generated_app(11);
+    app.insert_resource(players(6));
+    begin(&mut app);
+    for index in 0..6 {
+        assert!(!bases_of(&mut app, index).is_empty());
+    }
+    // A full round reaches every player
+    let mut owners = Vec::new();
+    for _ in 0..6 {
+        let base = app.world.get_resource::<TurnState>().unwrap().active_base.unwrap();
+        owners.push(app.world.get::<BaseOwner>(base).unwrap().entity);
+        send(&mut app, TurnEnd);
+        step(&mut app, 2);
+    }
+    for index in 0..6 {
+        assert!(owners.contains(&player(&app, index)));
+    }
+}
+
+#[test]
+fn map_with_too_few_slots_is_replaced_by_a_generated_one() {
+    let mut app = match_app(DUEL_MAP);
+    app.insert_resource(players(3));
+    begin(&mut app);
+    for index in 0..3 {
+        assert!(!bases_of(&mut app, index).is_empty());
+    }
+}
+
+#[test]
+fn generated_maps_share_bases_evenly_at_the_player_limit() {
+    for seed in [1, 2, 3, 5, 8, 13, 21, 34, 55, 89] {
+        let layout = generate_map(&MapSettings::default(), seed, MAX_PLAYERS);
+        let mut counts = vec![0; MAX_PLAYERS];
+        for asteroid in layout.asteroids.iter() {
+            for base in asteroid.bases.iter() {
+                counts[base.player] += 1;
+            }
+        }
+        assert!(counts[0] > 0, "seed {} gave no bases", seed);
+        assert!(counts.iter().all(|count| *count == counts[0]), "seed {} gave {:?}", seed, counts);
+    }
+}
+
+#[test]
+fn spare_map_slots_are_shared_between_the_players() {
+    // Four slots with a base each, between two players
+    let mut app = match_app("assets/maps/belt.ron");
+    begin(&mut app);
+    let bases = app.world.query::<&Base>().iter(&app.world).count();
+    assert_eq!(bases, 4);
+    assert_eq!(bases_of(&mut app, 0).len(), 2);
+    assert_eq!(bases_of(&mut app, 1).len(), 2);
+}
+
+#[test]
+fn map_slots_that_can_not_be_shared_evenly_are_replaced_by_a_generated_map() {
+    let mut app = match_app("assets/maps/belt.ron");
+    app.insert_resource(players(3));
+    begin(&mut app);
+    let counts : Vec<usize> = (0..3).map(|index| bases_of(&mut app, index).len()).collect();
+    assert!(counts[0] > 0);
+    assert!(counts.iter().all(|count| *count == counts[0]));
+}
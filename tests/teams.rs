@@ -0,0 +1,137 @@
+// Playing in teams which win together and can be kept from hurting each other
+
+mod common;
+
+use bevy::prelude::*;
+
+use rasteroids_game::app_state::AppState;
+use rasteroids_game::base::{Base, BaseDestroyed};
+use rasteroids_game::explosion::Explode;
+use rasteroids_game::player::{Player, PlayerSettings, Victory};
+use rasteroids_game::weapon::{Homing, Weapon, WeaponType};
+
+use common::*;
+
+// Four players, the first and third against the second and fourth
+fn team_settings(friendly_fire : bool) -> PlayerSettings {
+    let mut settings = PlayerSettings::default();
+    while settings.players.len() < 4 {
+        settings.add();
+    }
+    settings.teams = true;
+    settings.friendly_fire = friendly_fire;
+    settings
+}
+
+fn team_match(friendly_fire : bool) -> App {
+    let mut app = generated_app(11);
+    app.insert_resource(team_settings(friendly_fire));
+    begin(&mut app);
+    app
+}
+
+// Explosion on top of a base, set off by the player in the given turn order slot
+fn blast(app : &mut App, base : Entity, source : usize) {
+    let pos = position(app, base);
+    let source = player(app, source);
+    send(app, Explode { pos : pos, power : 40.0, asteroid_multiplier : 0.0, source : Some(source) });
+    step(app, 2);
+}
+
+fn lose_all_bases(app : &mut App, index : usize) {
+    for base in bases_of(app, index) {
+        send(app, BaseDestroyed { base : base });
+    }
+}
+
+#[test]
+fn everyone_plays_alone_unless_there_are_two_teams() {
+    let mut settings = team_settings(false);
+    assert_eq!(settings.team_of(2), 0);
+    assert_eq!(settings.team_of(3), 1);
+    for index in 0..4 {
+        while settings.players[index].team != 0 {
+            settings.next_team(index);
+        }
+    }
+    assert_eq!(settings.team_of(3), 3);
+    settings.teams = false;
+    settings.next_team(3);
+    assert_eq!(settings.team_of(3), 3);
+}
+
+#[test]
+fn team_mates_can_not_hurt_each_other_without_friendly_fire() {
+    let mut app = team_match(false);
+    let base = bases_of(&mut app, 2)[0];
+    blast(&mut app, base, 0);
+    assert_eq!(base_health(&app, base), 100.0);
+    blast(&mut app, base, 1);
+    assert!(base_health(&app, base) < 100.0);
+}
+
+#[test]
+fn players_still_hurt_their_own_bases() {
+    let mut app = team_match(false);
+    let base = bases_of(&mut app, 2)[0];
+    blast(&mut app, base, 2);
+    assert!(base_health(&app, base) < 100.0);
+}
+
+#[test]
+fn friendly_fire_hurts_team_mates() {
+    let mut app = team_match(true);
+    let base = bases_of(&mut app, 2)[0];
+    blast(&mut app, base, 0);
+    assert!(base_health(&app, base) < 100.0);
+}
+
+#[test]
+fn homing_rockets_leave_team_mates_alone() {
+    let mut app = team_match(true);
+    // With only the team's own bases left there is nothing to home in on
+    for index in [1, 3] {
+        for base in bases_of(&mut app, index) {
+            app.world.entity_mut(base).remove::<Base>();
+        }
+    }
+    let base = bases_of(&mut app, 0)[0];
+    launch(&mut app, base, 0.0, 70.0, WeaponType::HomingRocket);
+    launch(&mut app, base, 0.0, 70.0, WeaponType::Rocket);
+    step(&mut app, 20);
+    let mut thrusts : Vec<(bool, Vec2)> = app.world.query::<(&Weapon, Option<&Homing>)>().iter(&app.world).map(
+        |(weapon, homing)| (homing.is_some(), weapon.thrust)
+    ).collect();
+    thrusts.sort_by_key(|(homing, _)| *homing);
+    assert_eq!(thrusts.len(), 2);
+    assert!(thrusts[1].0);
+    assert!(thrusts[0].1.distance(thrusts[1].1) < 0.01);
+}
+
+#[test]
+fn match_goes_on_while_a_team_has_bases() {
+    let mut app = team_match(false);
+    lose_all_bases(&mut app, 1);
+    step(&mut app, 3);
+    assert_eq!(state(&app), AppState::InGame);
+}
+
+#[test]
+fn whole_team_shares_the_victory() {
+    let mut app = team_match(false);
+    let expected : Vec<String> = [0, 2].iter().map(
+        |index| app.world.get::<Player>(player(&app, *index)).unwrap().name.clone()
+    ).collect();
+    // The third player is knocked out but their team mate wins for them
+    lose_all_bases(&mut app, 2);
+    step(&mut app, 3);
+    assert_eq!(state(&app), AppState::InGame);
+    lose_all_bases(&mut app, 1);
+    lose_all_bases(&mut app, 3);
+    step(&mut app, 3);
+    assert_eq!(state(&app), AppState::VictoryMenu);
+    let victory = app.world.get_resource::<Victory>().unwrap();
+    assert_eq!(victory.team, Some(0));
+    let members : Vec<String> = victory.members.iter().map(|player| player.name.clone()).collect();
+    assert_eq!(members, expected);
+}
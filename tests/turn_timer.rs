@@ -0,0 +1,77 @@
+// Players who take too long to aim
+
+mod common;
+
+use bevy::prelude::*;
+
+use rasteroids_game::game::TurnAction;
+use rasteroids_game::inventory::Inventory;
+use rasteroids_game::turn::{TurnState, TurnPhase, TurnTimerSettings, TimeoutAction};
+use rasteroids_game::weapon::{Weapon, WeaponType};
+
+use common::*;
+
+fn timed_match(limit : Option<f32>, on_timeout : TimeoutAction) -> App {
+    let mut app = match_app(DUEL_MAP);
+    app.insert_resource(TurnTimerSettings { limit : limit, on_timeout : on_timeout });
+    begin(&mut app);
+    app
+}
+
+fn rockets_left(app : &App, index : usize) -> u32 {
+    app.world.get::<Inventory>(player(app, index)).unwrap().count(WeaponType::Rocket)
+}
+
+#[test]
+fn no_limit_waits_for_the_player() {
+    let mut app = timed_match(None, TimeoutAction::Fire);
+    let base = bases_of(&mut app, 0)[0];
+    step(&mut app, 10);
+    let turn_state = app.world.get_resource::<TurnState>().unwrap();
+    assert_eq!(turn_state.phase, TurnPhase::Aiming);
+    assert_eq!(turn_state.active_base, Some(base));
+    assert_eq!(turn_state.time_left, None);
+}
+
+#[test]
+fn running_out_of_time_fires_the_selected_weapon() {
+    let mut app = timed_match(Some(0.0), TimeoutAction::Fire);
+    step(&mut app, 3);
+    assert_eq!(rockets_left(&app, 0), 19);
+    assert_eq!(app.world.query::<&Weapon>().iter(&app.world).count(), 1);
+    assert_eq!(app.world.get_resource::<TurnState>().unwrap().phase, TurnPhase::Firing);
+}
+
+#[test]
+fn running_out_of_time_can_skip_the_turn() {
+    let mut app = timed_match(Some(0.0), TimeoutAction::Skip);
+    let mut bases = bases_of(&mut app, 0);
+    bases.extend(bases_of(&mut app, 1));
+    // With no time at all every turn is skipped as soon as it starts, and the turns keep
+    // going round every base
+    let mut active = Vec::new();
+    for _ in 0..12 {
+        step(&mut app, 1);
+        active.push(app.world.get_resource::<TurnState>().unwrap().active_base);
+    }
+    for base in bases {
+        assert!(active.contains(&Some(base)));
+    }
+    assert_eq!(rockets_left(&app, 0), 20);
+    assert_eq!(rockets_left(&app, 1), 20);
+    assert_eq!(app.world.query::<&Weapon>().iter(&app.world).count(), 0);
+}
+
+#[test]
+fn skipped_turn_leaves_the_next_player_aiming() {
+    let mut app = timed_match(None, TimeoutAction::Skip);
+    let other_base = bases_of(&mut app, 1)[0];
+    let next_base = bases_of(&mut app, 0)[1];
+    for base in [other_base, next_base] {
+        send(&mut app, TurnAction::Skip);
+        step(&mut app, 3);
+        let turn_state = app.world.get_resource::<TurnState>().unwrap();
+        assert_eq!(turn_state.active_base, Some(base));
+        assert_eq!(turn_state.phase, TurnPhase::Aiming);
+    }
+}